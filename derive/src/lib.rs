@@ -0,0 +1,408 @@
+//! Derive macros for the [arguably](https://docs.rs/arguably) argument-parsing library.
+//!
+//! This crate is re-exported by `arguably` when its `derive` feature is enabled. See the
+//! documentation for the `arguably::Cli` and `arguably::Commands` traits for details.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, GenericArgument, LitStr,
+    PathArguments, Type};
+
+
+/// Implements `arguably::Cli` for a struct with named fields.
+#[proc_macro_derive(Cli, attributes(cli, arg))]
+pub fn derive_cli(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_cli(&input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+
+/// Implements `arguably::Commands` for an enum.
+#[proc_macro_derive(Commands, attributes(arg))]
+pub fn derive_commands(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_commands(&input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+
+// Settings from a struct's `#[cli(...)]` attributes.
+#[derive(Default)]
+struct CliAttrs {
+    name: Option<String>,
+    version: Option<String>,
+    helptext: Option<String>,
+    footer: Option<String>,
+    generate_helptext: bool,
+}
+
+
+// Settings from a field's or variant's `#[arg(...)]` attributes.
+#[derive(Default)]
+struct ArgAttrs {
+    name: Option<String>,
+    default: Option<String>,
+    help: Option<String>,
+    value_name: Option<String>,
+    env: Option<String>,
+    required: bool,
+    count: bool,
+    positional: bool,
+    command: bool,
+}
+
+
+fn parse_cli_attrs(attrs: &[Attribute]) -> syn::Result<CliAttrs> {
+    let mut result = CliAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("cli")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                result.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("version") {
+                result.version = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("helptext") {
+                result.helptext = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("footer") {
+                result.footer = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("generate_helptext") {
+                result.generate_helptext = true;
+            } else {
+                return Err(meta.error("unsupported cli attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(result)
+}
+
+
+fn parse_arg_attrs(attrs: &[Attribute]) -> syn::Result<ArgAttrs> {
+    let mut result = ArgAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("arg")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                result.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("default") {
+                result.default = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("help") {
+                result.help = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("value_name") {
+                result.value_name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("env") {
+                result.env = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("required") {
+                result.required = true;
+            } else if meta.path.is_ident("count") {
+                result.count = true;
+            } else if meta.path.is_ident("positional") {
+                result.positional = true;
+            } else if meta.path.is_ident("command") {
+                result.command = true;
+            } else {
+                return Err(meta.error("unsupported arg attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    if result.help.is_none() {
+        result.help = doc_comment(attrs);
+    }
+    Ok(result)
+}
+
+
+// Returns the item's doc comment, if any, joined into a single line.
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let mut lines = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("doc")) {
+        if let syn::Meta::NameValue(meta) = &attr.meta {
+            if let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) = &meta.value {
+                let line = lit.value().trim().to_string();
+                if !line.is_empty() {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+    if lines.is_empty() { None } else { Some(lines.join(" ")) }
+}
+
+
+// The kinds of field type the derive macro distinguishes between.
+enum FieldType<'a> {
+    Bool,
+    String,
+    Option(&'a Type),
+    Vec(&'a Type),
+    Other,
+}
+
+
+fn classify(ty: &Type) -> FieldType<'_> {
+    if let Type::Path(type_path) = ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            let inner = match &segment.arguments {
+                PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(GenericArgument::Type(inner)) => Some(inner),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match (segment.ident.to_string().as_str(), inner) {
+                ("bool", None) => return FieldType::Bool,
+                ("String", None) => return FieldType::String,
+                ("Option", Some(inner)) => return FieldType::Option(inner),
+                ("Vec", Some(inner)) => return FieldType::Vec(inner),
+                _ => {}
+            }
+        }
+    }
+    FieldType::Other
+}
+
+
+// Converts an identifier like `dry_run` or `DryRun` into a command line name like `dry-run`.
+fn kebab_case(ident: &str) -> String {
+    let mut result = String::new();
+    for (index, c) in ident.trim_start_matches("r#").chars().enumerate() {
+        if c == '_' {
+            result.push('-');
+        } else if c.is_uppercase() {
+            if index > 0 && !result.ends_with('-') {
+                result.push('-');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+
+fn expand_cli(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(
+                ident, "Cli can only be derived for structs with named fields"
+            )),
+        },
+        _ => return Err(syn::Error::new_spanned(ident, "Cli can only be derived for structs")),
+    };
+
+    let cli_attrs = parse_cli_attrs(&input.attrs)?;
+    let mut setup = Vec::new();
+    if let Some(name) = &cli_attrs.name {
+        setup.push(quote! { parser = parser.name(#name); });
+    }
+    if let Some(version) = &cli_attrs.version {
+        setup.push(quote! { parser = parser.version(#version); });
+    }
+    if let Some(helptext) = &cli_attrs.helptext {
+        setup.push(quote! { parser = parser.helptext(#helptext); });
+    }
+    if let Some(footer) = &cli_attrs.footer {
+        setup.push(quote! { parser = parser.footer(#footer); });
+    }
+    if cli_attrs.generate_helptext {
+        setup.push(quote! { parser = parser.generate_helptext(true); });
+    }
+
+    let mut registrations = Vec::new();
+    let mut initializers = Vec::new();
+
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let attrs = parse_arg_attrs(&field.attrs)?;
+        let ty = &field.ty;
+
+        if attrs.command {
+            let (enum_ty, optional) = match classify(ty) {
+                FieldType::Option(inner) => (inner, true),
+                _ => (ty, false),
+            };
+            registrations.push(quote! {
+                parser = <#enum_ty as ::arguably::Commands>::register_commands(parser);
+            });
+            let missing = if optional {
+                quote! { None }
+            } else {
+                quote! {
+                    return Err(::arguably::Error::new(
+                        ::arguably::ErrorKind::MissingRequired, "missing command"
+                    ))
+                }
+            };
+            let found = if optional {
+                quote! { Some(<#enum_ty as ::arguably::Commands>::from_command(name, cmd_parser)?) }
+            } else {
+                quote! { <#enum_ty as ::arguably::Commands>::from_command(name, cmd_parser)? }
+            };
+            initializers.push(quote! {
+                #field_ident: match (&parser.cmd_name, &parser.cmd_parser) {
+                    (Some(name), Some(cmd_parser)) => #found,
+                    _ => #missing,
+                }
+            });
+            continue;
+        }
+
+        if attrs.positional {
+            let inner = match classify(ty) {
+                FieldType::Vec(inner) => inner,
+                _ => return Err(syn::Error::new_spanned(
+                    ty, "positional fields must have type Vec<T>"
+                )),
+            };
+            initializers.push(quote! {
+                #field_ident: (0..parser.args.len())
+                    .map(|index| parser.arg_as::<#inner>(index))
+                    .collect::<Result<Vec<#inner>, ::arguably::Error>>()?
+            });
+            continue;
+        }
+
+        let names = attrs.name.clone().unwrap_or_else(|| kebab_case(&field_ident.to_string()));
+        let first = match names.split_whitespace().next() {
+            Some(first) => first.to_string(),
+            None => return Err(syn::Error::new_spanned(field_ident, "name must not be empty")),
+        };
+
+        let field_type = classify(ty);
+        let is_flag = attrs.count || matches!(field_type, FieldType::Bool);
+
+        if is_flag {
+            registrations.push(quote! { parser = parser.flag(#names); });
+        } else {
+            let default = attrs.default.clone().unwrap_or_default();
+            registrations.push(quote! { parser = parser.option(#names, #default); });
+            let needs_value = matches!(field_type, FieldType::Other) && attrs.default.is_none();
+            if attrs.required || needs_value {
+                registrations.push(quote! { parser = parser.required(#first); });
+            }
+            if let Some(value_name) = &attrs.value_name {
+                registrations.push(quote! { parser = parser.value_name(#first, #value_name); });
+            }
+        }
+        if let Some(help) = &attrs.help {
+            registrations.push(quote! { parser = parser.describe(#first, #help); });
+        }
+        if let Some(env) = &attrs.env {
+            registrations.push(quote! { parser = parser.env(#first, #env); });
+        }
+
+        let value = if attrs.count {
+            quote! { parser.count(#first) }
+        } else {
+            match field_type {
+                FieldType::Bool => quote! { parser.found(#first) },
+                FieldType::String => quote! { parser.value(#first) },
+                FieldType::Option(inner) => quote! {
+                    if parser.found(#first) {
+                        Some(parser.value_as::<#inner>(#first)?)
+                    } else {
+                        None
+                    }
+                },
+                FieldType::Vec(inner) => quote! { parser.values_as::<#inner>(#first)? },
+                FieldType::Other => quote! { parser.value_as::<#ty>(#first)? },
+            }
+        };
+        initializers.push(quote! { #field_ident: #value });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::arguably::Cli for #ident #ty_generics #where_clause {
+            fn parser() -> ::arguably::ArgParser {
+                #[allow(unused_mut)]
+                let mut parser = ::arguably::ArgParser::new();
+                #(#setup)*
+                #(#registrations)*
+                parser
+            }
+
+            fn from_parser(parser: &::arguably::ArgParser) -> Result<Self, ::arguably::Error> {
+                Ok(#ident {
+                    #(#initializers,)*
+                })
+            }
+        }
+    })
+}
+
+
+fn expand_commands(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => return Err(syn::Error::new_spanned(ident, "Commands can only be derived for enums")),
+    };
+
+    let mut registrations = Vec::new();
+    let mut arms = Vec::new();
+
+    for variant in variants {
+        let variant_ident = &variant.ident;
+        let attrs = parse_arg_attrs(&variant.attrs)?;
+        let names = attrs.name.clone().unwrap_or_else(|| kebab_case(&variant_ident.to_string()));
+        let aliases: Vec<&str> = names.split_whitespace().collect();
+        let first = match aliases.first() {
+            Some(first) => first.to_string(),
+            None => return Err(syn::Error::new_spanned(variant_ident, "name must not be empty")),
+        };
+
+        let (cmd_parser, value) = match &variant.fields {
+            Fields::Unit => (
+                quote! { ::arguably::ArgParser::new() },
+                quote! { #ident::#variant_ident },
+            ),
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                (
+                    quote! { <#ty as ::arguably::Cli>::parser() },
+                    quote! {
+                        #ident::#variant_ident(<#ty as ::arguably::Cli>::from_parser(parser)?)
+                    },
+                )
+            }
+            _ => return Err(syn::Error::new_spanned(
+                variant_ident, "command variants must be unit variants or wrap a single Cli struct"
+            )),
+        };
+
+        registrations.push(quote! { parser = parser.command(#names, #cmd_parser); });
+        if let Some(help) = &attrs.help {
+            registrations.push(quote! { parser = parser.describe(#first, #help); });
+        }
+        arms.push(quote! { #(#aliases)|* => Ok(#value), });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::arguably::Commands for #ident #ty_generics #where_clause {
+            fn register_commands(parser: ::arguably::ArgParser) -> ::arguably::ArgParser {
+                #[allow(unused_mut)]
+                let mut parser = parser;
+                #(#registrations)*
+                parser
+            }
+
+            fn from_command(
+                name: &str,
+                parser: &::arguably::ArgParser,
+            ) -> Result<Self, ::arguably::Error> {
+                let _ = parser;
+                match name {
+                    #(#arms)*
+                    _ => Err(::arguably::Error::new(
+                        ::arguably::ErrorKind::InvalidName,
+                        format!("'{}' is not a recognised command name", name),
+                    )),
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,93 @@
+use arguably::{Cli, Commands, ErrorKind};
+
+#[derive(Cli)]
+#[cli(name = "app", version = "1.0")]
+struct Args {
+    /// Enables verbose output.
+    #[arg(name = "verbose v")]
+    verbose: bool,
+
+    #[arg(name = "debug d", count)]
+    debug: usize,
+
+    #[arg(name = "output o", default = "out.txt")]
+    output: String,
+
+    #[arg(name = "port p", default = "8080")]
+    port: u16,
+
+    #[arg(name = "limit")]
+    limit: Option<u32>,
+
+    #[arg(name = "tag t")]
+    tags: Vec<String>,
+
+    #[arg(positional)]
+    files: Vec<String>,
+
+    #[arg(command)]
+    command: Option<Command>,
+}
+
+#[derive(Commands, Debug, PartialEq)]
+enum Command {
+    /// Builds the project.
+    #[arg(name = "build b")]
+    Build(BuildArgs),
+    DryRun,
+}
+
+#[derive(Cli, Debug, PartialEq)]
+struct BuildArgs {
+    #[arg(name = "release r")]
+    release: bool,
+    jobs: usize,
+}
+
+#[test]
+fn derive_defaults() {
+    let args = Args::parse_vec(vec![]).unwrap();
+    assert!(!args.verbose);
+    assert_eq!(args.debug, 0);
+    assert_eq!(args.output, "out.txt");
+    assert_eq!(args.port, 8080);
+    assert_eq!(args.limit, None);
+    assert!(args.tags.is_empty());
+    assert!(args.files.is_empty());
+    assert_eq!(args.command, None);
+}
+
+#[test]
+fn derive_values() {
+    let args = Args::parse_vec(vec![
+        "-v", "-dd", "--output", "foo", "-p", "80", "--limit", "5", "-t", "a", "-t", "b", "file",
+    ]).unwrap();
+    assert!(args.verbose);
+    assert_eq!(args.debug, 2);
+    assert_eq!(args.output, "foo");
+    assert_eq!(args.port, 80);
+    assert_eq!(args.limit, Some(5));
+    assert_eq!(args.tags, vec!["a", "b"]);
+    assert_eq!(args.files, vec!["file"]);
+}
+
+#[test]
+fn derive_commands() {
+    let args = Args::parse_vec(vec!["b", "--release", "--jobs", "4"]).unwrap();
+    assert_eq!(args.command, Some(Command::Build(BuildArgs { release: true, jobs: 4 })));
+
+    let args = Args::parse_vec(vec!["dry-run"]).unwrap();
+    assert_eq!(args.command, Some(Command::DryRun));
+}
+
+#[test]
+fn derive_errors() {
+    assert!(matches!(Args::parse_vec(vec!["--port", "abc"]).map_err(|err| err.kind()), Err(ErrorKind::InvalidValue)));
+    assert!(matches!(Args::parse_vec(vec!["build"]).map_err(|err| err.kind()), Err(ErrorKind::MissingRequired)));
+}
+
+#[test]
+fn derive_helptext() {
+    let parser = Args::parser().generate_helptext(true);
+    assert!(parser.render_helptext().contains("Enables verbose output."));
+}
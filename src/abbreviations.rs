@@ -0,0 +1,136 @@
+// Support for abbreviated long-form flag, option, and command names.
+
+use crate::ArgParser;
+use crate::Error;
+
+
+// The flag, option, or command a candidate name resolves to. Aliases of the same flag,
+// option, or command share a target, so a prefix matching several of them is unambiguous.
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Flag(usize),
+    Negation(usize),
+    Option(usize),
+    Command(usize),
+    Help,
+    Version,
+}
+
+
+impl ArgParser {
+    /// This boolean switch toggles support for abbreviated long-form flag, option, and command
+    /// names. If enabled, any unambiguous prefix of a registered name is accepted in its place,
+    /// so `--verb` is parsed as `--verbose` and `appname sta` as `appname status`. Parsing will
+    /// fail with an `InvalidName` error listing the candidates if a prefix is ambiguous.
+    /// Command parsers inherit the setting from their parent. The value defaults to `false`.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .allow_abbreviations(true)
+    ///     .flag("verbose")
+    ///     .command("status", ArgParser::new());
+    ///
+    /// parser.parse_vec(vec!["--verb", "sta"]).unwrap();
+    /// assert!(parser.found("verbose"));
+    /// assert_eq!(parser.cmd_name, Some("status".to_string()));
+    /// ```
+    pub fn allow_abbreviations(mut self, enable: bool) -> Self {
+        self.allow_abbreviations = enable;
+        self
+    }
+
+    // Expands an abbreviated long-form name in an argument like `--verb` or `--verb=value`.
+    // Arguments which do not abbreviate a registered name are returned unchanged.
+    pub(crate) fn expand_long_arg(&self, arg: &str) -> Result<String, Error> {
+        if !self.allow_abbreviations {
+            return Ok(arg.to_string());
+        }
+
+        let (name, value) = match arg[2..].split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (&arg[2..], None),
+        };
+
+        let mut candidates = Vec::new();
+        for (alias, index) in &self.option_map {
+            candidates.push((alias.clone(), Target::Option(*index)));
+        }
+        if value.is_none() {
+            for (alias, index) in &self.flag_map {
+                candidates.push((alias.clone(), Target::Flag(*index)));
+            }
+            for (index, flag) in self.flags.iter().enumerate() {
+                for negation in flag.negations() {
+                    candidates.push((negation, Target::Negation(index)));
+                }
+            }
+            if self.has_helptext() && !self.is_registered("help") {
+                candidates.push(("help".to_string(), Target::Help));
+            }
+            if self.version.is_some() && !self.is_registered("version") {
+                candidates.push(("version".to_string(), Target::Version));
+            }
+        }
+        candidates.retain(|(alias, _)| alias.chars().count() > 1);
+
+        match expand(name, candidates) {
+            Ok(Some(name)) => Ok(match value {
+                Some(value) => format!("--{}={}", name, value),
+                None => format!("--{}", name),
+            }),
+            Ok(None) => Ok(arg.to_string()),
+            Err(names) => {
+                let names: Vec<String> = names.iter().map(|name| format!("--{}", name)).collect();
+                Err(crate::invalid_name(format!("--{} is ambiguous", name), names))
+            }
+        }
+    }
+
+    // Expands an abbreviated command name. Names which do not abbreviate a registered command
+    // are returned unchanged. The hidden `completion` command can only be used in full.
+    pub(crate) fn expand_command_name(&self, name: &str) -> Result<String, Error> {
+        if !self.allow_abbreviations || self.cmd_completion && name == "completion" {
+            return Ok(name.to_string());
+        }
+
+        let mut candidates: Vec<(String, Target)> = self.command_map.iter()
+            .map(|(alias, index)| (alias.clone(), Target::Command(*index)))
+            .collect();
+        if self.cmd_help {
+            candidates.push(("help".to_string(), Target::Help));
+        }
+
+        match expand(name, candidates) {
+            Ok(Some(name)) => Ok(name),
+            Ok(None) => Ok(name.to_string()),
+            Err(names) => {
+                let msg = format!("'{}' is an ambiguous command name", name);
+                Err(crate::invalid_name(msg, names))
+            }
+        }
+    }
+}
+
+
+// Returns the candidate name matching `prefix`. An exact match is preferred, otherwise the
+// prefix must match the names of a single target. Returns `Ok(None)` if nothing matches or
+// the sorted list of matching names if the prefix is ambiguous.
+fn expand(prefix: &str, candidates: Vec<(String, Target)>) -> Result<Option<String>, Vec<String>> {
+    if prefix.is_empty() || candidates.iter().any(|(name, _)| name == prefix) {
+        return Ok(None);
+    }
+
+    let mut matches: Vec<(String, Target)> = candidates.into_iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .collect();
+    matches.sort_by(|a, b| a.0.cmp(&b.0));
+
+    match matches.first() {
+        None => Ok(None),
+        Some((name, target)) if matches.iter().all(|(_, other)| other == target) => {
+            Ok(Some(name.clone()))
+        }
+        Some(_) => Err(matches.into_iter().map(|(name, _)| name).collect()),
+    }
+}
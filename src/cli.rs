@@ -0,0 +1,92 @@
+// Traits implemented by the `derive` feature's macros.
+
+use crate::ArgParser;
+use crate::Error;
+
+
+/// A struct which declares a command line interface. This trait is usually implemented using
+/// `#[derive(Cli)]`, available with the `derive` feature.
+///
+/// The derive macro registers a flag for each `bool` field, or for each `usize` field marked
+/// `#[arg(count)]`, and an option for each `String`, `Option<T>`, `Vec<T>`, or other field
+/// whose type implements `FromStr`. Typed options without a default value are registered as
+/// required. A `Vec<T>` field marked `#[arg(positional)]` collects the positional arguments.
+/// A field marked `#[arg(command)]` registers the commands declared by an enum deriving
+/// `Commands`; wrap the enum in an `Option` if the command is optional.
+///
+/// Field attributes: `name = "..."` (space-separated aliases, defaulting to the field name
+/// with underscores replaced by dashes), `default = "..."`, `help = "..."` (defaulting to the
+/// field's doc comment), `value_name = "..."`, `env = "..."`, `required`, `count`,
+/// `positional`, `command`. Struct attributes, set using `#[cli(...)]`: `name = "..."`,
+/// `version = "..."`, `helptext = "..."`, `footer = "..."`, `generate_helptext`.
+///
+/// ```ignore
+/// use arguably::{Cli, Commands};
+///
+/// #[derive(Cli)]
+/// #[cli(name = "appname", version = "1.0", generate_helptext)]
+/// struct Args {
+///     /// Enables verbose output.
+///     #[arg(name = "verbose v")]
+///     verbose: bool,
+///
+///     #[arg(name = "port p", default = "8080")]
+///     port: u16,
+///
+///     #[arg(command)]
+///     command: Option<Command>,
+/// }
+///
+/// #[derive(Commands)]
+/// enum Command {
+///     /// Builds the project.
+///     Build(BuildArgs),
+///     Clean,
+/// }
+///
+/// #[derive(Cli)]
+/// struct BuildArgs {
+///     #[arg(name = "release r")]
+///     release: bool,
+/// }
+///
+/// let args = Args::parse_args().unwrap_or_else(|err| err.exit());
+/// ```
+pub trait Cli: Sized {
+    /// Returns a new `ArgParser` instance with the struct's flags, options, and commands
+    /// registered.
+    fn parser() -> ArgParser;
+
+    /// Builds an instance of the struct from a parser which has parsed its arguments.
+    fn from_parser(parser: &ArgParser) -> Result<Self, Error>;
+
+    /// Parses the program's command line arguments.
+    fn parse_args() -> Result<Self, Error> {
+        let mut parser = Self::parser();
+        parser.parse()?;
+        Self::from_parser(&parser)
+    }
+
+    /// Parses a vector of arguments.
+    fn parse_vec(args: Vec<&str>) -> Result<Self, Error> {
+        let mut parser = Self::parser();
+        parser.parse_vec(args)?;
+        Self::from_parser(&parser)
+    }
+}
+
+
+/// An enum which declares a set of commands. This trait is usually implemented using
+/// `#[derive(Commands)]`, available with the `derive` feature.
+///
+/// Each variant registers a command, named after the variant in lowercase with words
+/// separated by dashes. A variant can wrap a single struct implementing `Cli` which declares
+/// the command's own interface. Variant attributes: `name = "..."` (space-separated
+/// aliases), `help = "..."` (defaulting to the variant's doc comment).
+pub trait Commands: Sized {
+    /// Registers the enum's commands on a parser.
+    fn register_commands(parser: ArgParser) -> ArgParser;
+
+    /// Builds an instance of the enum from the name and parser of the command found.
+    fn from_command(name: &str, parser: &ArgParser) -> Result<Self, Error>;
+}
@@ -0,0 +1,358 @@
+// Support for generating shell completion scripts.
+
+use crate::ArgParser;
+use crate::Error;
+use crate::ErrorKind;
+use crate::globals::Inherited;
+use std::str::FromStr;
+
+
+/// Shells supported by the completion script generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+
+impl FromStr for Shell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(Error::new(ErrorKind::InvalidValue,
+                format!("'{}' is not a supported shell (expected bash, zsh, or fish)", s)
+            )),
+        }
+    }
+}
+
+
+// The completion candidates for a single parser in the command tree. Each level is
+// identified by a key built from the program name and the canonical command names leading
+// to it, e.g. `app_remote_add`.
+struct Level {
+    key: String,
+    flags: Vec<(Vec<String>, String)>,
+    options: Vec<(Vec<String>, String, Vec<String>)>,
+    commands: Vec<(Vec<String>, String, String)>,
+}
+
+
+impl ArgParser {
+    /// Returns a static completion script for the specified shell. The script covers the
+    /// parser's flags, options, and commands, including nested commands.
+    ///
+    /// ```
+    /// # use arguably::{ArgParser, Shell};
+    /// let parser = ArgParser::new()
+    ///     .name("appname")
+    ///     .flag("foo f");
+    ///
+    /// let script = parser.completion_script(Shell::Bash);
+    /// ```
+    pub fn completion_script(&self, shell: Shell) -> String {
+        let name = self.app_name();
+        let mut levels = Vec::new();
+        self.collect_levels(&sanitize(&name), &Inherited::default(), &mut levels);
+        match shell {
+            Shell::Bash => bash_script(&name, &levels),
+            Shell::Zsh => zsh_script(&name, &levels),
+            Shell::Fish => fish_script(&name, &levels),
+        }
+    }
+
+    /// This boolean switch toggles support for a hidden `completion` command that prints a
+    /// completion script for the shell named by its argument, e.g. `appname completion bash`.
+    /// The value defaults to `false`.
+    pub fn enable_completion_command(mut self, enable: bool) -> Self {
+        self.cmd_completion = enable;
+        self
+    }
+
+    fn collect_levels(&self, key: &str, inherited: &Inherited, levels: &mut Vec<Level>) {
+        let mut level = Level {
+            key: key.to_string(),
+            flags: Vec::new(),
+            options: Vec::new(),
+            commands: Vec::new(),
+        };
+
+        for flag in self.flags_with(inherited) {
+            level.flags.push((flag.names.clone(), flag.description.clone().unwrap_or_default()));
+            let negations = flag.negations();
+            if !negations.is_empty() {
+                level.flags.push((negations, String::new()));
+            }
+        }
+        if self.has_helptext() {
+            let names = self.unregistered(&["help", "h"]);
+            if !names.is_empty() {
+                level.flags.push((names, "Print the helptext and exit.".to_string()));
+            }
+        }
+        if self.version.is_some() {
+            let names = self.unregistered(&["version", "v"]);
+            if !names.is_empty() {
+                level.flags.push((names, "Print the version number and exit.".to_string()));
+            }
+        }
+
+        for opt in self.options_with(inherited) {
+            // Options with implicit values never consume the following argument so we
+            // complete them like flags.
+            if opt.implicit_value.is_some() {
+                level.flags.push((opt.names.clone(), opt.description.clone().unwrap_or_default()));
+                continue;
+            }
+            level.options.push((
+                opt.names.clone(),
+                opt.description.clone().unwrap_or_default(),
+                opt.choices.clone(),
+            ));
+        }
+
+        let mut children = Vec::new();
+        for cmd_parser in &self.commands {
+            let canonical = cmd_parser.aliases.first().map(String::as_str).unwrap_or("");
+            let cmd_key = format!("{}_{}", key, sanitize(canonical));
+            let description = cmd_parser.description.clone().unwrap_or_default();
+            level.commands.push((cmd_parser.aliases.clone(), cmd_key.clone(), description));
+            children.push((cmd_parser, cmd_key));
+        }
+
+        levels.push(level);
+
+        let inherited = self.inherited_by_commands(inherited);
+        for (cmd_parser, cmd_key) in children {
+            cmd_parser.collect_levels(&cmd_key, &inherited, levels);
+        }
+    }
+}
+
+
+// Converts a name into a string safe for use as a shell identifier.
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+
+// Converts a list of aliases into dashed command line form, e.g. `-f --foo`.
+fn dashed(names: &[String]) -> Vec<String> {
+    names.iter()
+        .map(|name| match name.chars().count() {
+            1 => format!("-{}", name),
+            _ => format!("--{}", name),
+        })
+        .collect()
+}
+
+
+fn bash_script(name: &str, levels: &[Level]) -> String {
+    let func = format!("_{}", sanitize(name));
+    let root = &levels[0].key;
+    let mut out = String::new();
+
+    out.push_str(&format!("{}() {{\n", func));
+    out.push_str("    local cur prev cmdpath i\n");
+    out.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    out.push_str(&format!("    cmdpath=\"{}\"\n\n", root));
+
+    out.push_str("    for ((i = 1; i < COMP_CWORD; i++)); do\n");
+    out.push_str("        case \"${cmdpath}:${COMP_WORDS[i]}\" in\n");
+    for level in levels {
+        for (aliases, cmd_key, _) in &level.commands {
+            let patterns: Vec<String> = aliases.iter()
+                .map(|a| format!("{}:{}", level.key, a))
+                .collect();
+            out.push_str(&format!(
+                "            {}) cmdpath=\"{}\" ;;\n", patterns.join("|"), cmd_key
+            ));
+        }
+    }
+    out.push_str("        esac\n");
+    out.push_str("    done\n\n");
+
+    out.push_str("    case \"${cmdpath}:${prev}\" in\n");
+    for level in levels {
+        for (names, _, choices) in &level.options {
+            let patterns: Vec<String> = dashed(names).iter()
+                .map(|n| format!("{}:{}", level.key, n))
+                .collect();
+            if choices.is_empty() {
+                out.push_str(&format!("        {}) return ;;\n", patterns.join("|")));
+            } else {
+                out.push_str(&format!(
+                    "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\")); return ;;\n",
+                    patterns.join("|"), choices.join(" ")
+                ));
+            }
+        }
+    }
+    out.push_str("    esac\n\n");
+
+    out.push_str("    local words=\"\"\n");
+    out.push_str("    case \"${cmdpath}\" in\n");
+    for level in levels {
+        let mut words = Vec::new();
+        for (names, _) in &level.flags {
+            words.extend(dashed(names));
+        }
+        for (names, _, _) in &level.options {
+            words.extend(dashed(names));
+        }
+        for (aliases, _, _) in &level.commands {
+            words.extend(aliases.iter().cloned());
+        }
+        out.push_str(&format!("        {}) words=\"{}\" ;;\n", level.key, words.join(" ")));
+    }
+    out.push_str("    esac\n\n");
+
+    out.push_str("    COMPREPLY=($(compgen -W \"${words}\" -- \"${cur}\"))\n");
+    out.push_str("}\n\n");
+    out.push_str(&format!("complete -o default -F {} {}\n", func, name));
+    out
+}
+
+
+fn zsh_script(name: &str, levels: &[Level]) -> String {
+    let func = format!("_{}", sanitize(name));
+    let root = &levels[0].key;
+    let mut out = String::new();
+
+    out.push_str(&format!("#compdef {}\n\n", name));
+    out.push_str(&format!("{}() {{\n", func));
+    out.push_str("    local cmdpath i\n");
+    out.push_str(&format!("    cmdpath=\"{}\"\n\n", root));
+
+    out.push_str("    for ((i = 2; i < CURRENT; i++)); do\n");
+    out.push_str("        case \"${cmdpath}:${words[i]}\" in\n");
+    for level in levels {
+        for (aliases, cmd_key, _) in &level.commands {
+            let patterns: Vec<String> = aliases.iter()
+                .map(|a| format!("{}:{}", level.key, a))
+                .collect();
+            out.push_str(&format!(
+                "            ({}) cmdpath=\"{}\" ;;\n", patterns.join("|"), cmd_key
+            ));
+        }
+    }
+    out.push_str("        esac\n");
+    out.push_str("    done\n\n");
+
+    out.push_str("    case \"${cmdpath}:${words[CURRENT-1]}\" in\n");
+    for level in levels {
+        for (names, _, choices) in &level.options {
+            let patterns: Vec<String> = dashed(names).iter()
+                .map(|n| format!("{}:{}", level.key, n))
+                .collect();
+            if choices.is_empty() {
+                out.push_str(&format!("        ({}) _files; return ;;\n", patterns.join("|")));
+            } else {
+                out.push_str(&format!(
+                    "        ({}) compadd -- {}; return ;;\n", patterns.join("|"), choices.join(" ")
+                ));
+            }
+        }
+    }
+    out.push_str("    esac\n\n");
+
+    out.push_str("    case \"${cmdpath}\" in\n");
+    for level in levels {
+        let mut words = Vec::new();
+        for (names, _) in &level.flags {
+            words.extend(dashed(names));
+        }
+        for (names, _, _) in &level.options {
+            words.extend(dashed(names));
+        }
+        for (aliases, _, _) in &level.commands {
+            words.extend(aliases.iter().cloned());
+        }
+        out.push_str(&format!("        ({}) compadd -- {} ;;\n", level.key, words.join(" ")));
+    }
+    out.push_str("    esac\n");
+    out.push_str("}\n\n");
+    out.push_str(&format!("compdef {} {}\n", func, name));
+    out
+}
+
+
+fn fish_script(name: &str, levels: &[Level]) -> String {
+    let func = format!("__{}_cmdpath", sanitize(name));
+    let root = &levels[0].key;
+    let mut out = String::new();
+
+    out.push_str(&format!("function {}\n", func));
+    out.push_str("    set -l tokens (commandline -opc)\n");
+    out.push_str(&format!("    set -l cmdpath {}\n", root));
+    out.push_str("    for token in $tokens[2..-1]\n");
+    out.push_str("        switch \"$cmdpath:$token\"\n");
+    for level in levels {
+        for (aliases, cmd_key, _) in &level.commands {
+            let patterns: Vec<String> = aliases.iter()
+                .map(|a| format!("'{}:{}'", level.key, a))
+                .collect();
+            out.push_str(&format!("            case {}\n", patterns.join(" ")));
+            out.push_str(&format!("                set cmdpath {}\n", cmd_key));
+        }
+    }
+    out.push_str("        end\n");
+    out.push_str("    end\n");
+    out.push_str("    echo $cmdpath\n");
+    out.push_str("end\n\n");
+
+    for level in levels {
+        let condition = format!("-n 'test ({}) = {}'", func, level.key);
+        for (names, description) in &level.flags {
+            out.push_str(&format!(
+                "complete -c {} {}{}{}\n",
+                name, condition, fish_names(names), fish_desc(description)
+            ));
+        }
+        for (names, description, choices) in &level.options {
+            let choices = if choices.is_empty() {
+                String::new()
+            } else {
+                format!(" -f -a '{}'", choices.join(" "))
+            };
+            out.push_str(&format!(
+                "complete -c {} {}{} -r{}{}\n",
+                name, condition, fish_names(names), choices, fish_desc(description)
+            ));
+        }
+        for (aliases, _, description) in &level.commands {
+            for alias in aliases {
+                out.push_str(&format!(
+                    "complete -c {} {} -f -a '{}'{}\n",
+                    name, condition, alias, fish_desc(description)
+                ));
+            }
+        }
+    }
+
+    out
+}
+
+
+fn fish_names(names: &[String]) -> String {
+    names.iter()
+        .map(|name| match name.chars().count() {
+            1 => format!(" -s {}", name),
+            _ => format!(" -l {}", name),
+        })
+        .collect()
+}
+
+
+fn fish_desc(description: &str) -> String {
+    if description.is_empty() {
+        return String::new();
+    }
+    format!(" -d '{}'", description.replace('\\', "\\\\").replace('\'', "\\'"))
+}
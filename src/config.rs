@@ -0,0 +1,181 @@
+// Support for loading option and flag values from INI-style config files.
+
+use crate::ArgParser;
+use crate::Error;
+use crate::ErrorKind;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+
+// The parsed contents of one or more config files. Maps section names to keys to values.
+// Keys outside of any section belong to the root parser and are stored under the empty
+// section name.
+#[derive(Clone, Default)]
+pub(crate) struct Config {
+    sections: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+
+impl Config {
+    // Returns the values for the first of `names` found in `section`.
+    pub(crate) fn get(&self, section: &str, names: &[String]) -> Option<&Vec<String>> {
+        let entries = self.sections.get(section)?;
+        names.iter().find_map(|name| entries.get(name))
+    }
+
+    // Parses a config file and merges its entries into the config. Entries from the file
+    // replace any existing entries with the same section and key.
+    fn merge_file(&mut self, path: &Path) -> Result<(), Error> {
+        let text = std::fs::read_to_string(path).map_err(|err| Error::new(ErrorKind::InvalidConfig,
+            format!("cannot read config file {}: {}", path.display(), err)
+        ))?;
+
+        let mut file_sections: HashMap<String, HashMap<String, Vec<String>>> = HashMap::new();
+        let mut section = String::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                if !line.ends_with(']') {
+                    return Err(Error::new(ErrorKind::InvalidConfig, format!(
+                        "{}:{}: unterminated section header", path.display(), index + 1
+                    )));
+                }
+                section = line[1..line.len() - 1].trim().to_string();
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), unquote(value.trim())),
+                None => return Err(Error::new(ErrorKind::InvalidConfig, format!(
+                    "{}:{}: expected 'key = value'", path.display(), index + 1
+                ))),
+            };
+
+            file_sections
+                .entry(section.clone())
+                .or_default()
+                .entry(key.to_string())
+                .or_default()
+                .push(value.to_string());
+        }
+
+        for (section, entries) in file_sections {
+            self.sections.entry(section).or_default().extend(entries);
+        }
+
+        Ok(())
+    }
+}
+
+
+// Strips a single pair of matching quotes from a value.
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+
+impl ArgParser {
+    /// Registers a config file to load default values from. Files are loaded in the order
+    /// they are registered, with entries in later files overriding entries in earlier files.
+    /// Missing files are ignored. Values from config files are overridden by environment
+    /// variables and the command line. A command parser inherits its parent's config, with
+    /// entries from its own config files and config option taking precedence.
+    ///
+    /// Config files consist of `key = value` lines where each key is a registered flag or
+    /// option name. Entries for commands go in sections named after the command, e.g.
+    /// `[cmdname]`, or `[cmdname.subcmdname]` for nested commands. Lines beginning with `#`
+    /// or `;` are comments. Multiple values for an option can be supplied by repeating the
+    /// key.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("output o", "")
+    ///     .config_file("/etc/appname.conf")
+    ///     .config_file("/home/user/.appname.conf");
+    /// ```
+    pub fn config_file<P>(mut self, path: P) -> Self where P: Into<PathBuf> {
+        self.config_files.push(path.into());
+        self
+    }
+
+    /// Registers an option whose value names a config file to load, e.g. `--config <path>`.
+    /// This file is loaded after any files registered with `.config_file()` and must exist.
+    /// The option's value can be supplied on the command line or by its environment
+    /// variable, but not by a config file.
+    /// (This function will panic if `name` is not a registered option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("config", "")
+    ///     .config_option("config");
+    /// ```
+    pub fn config_option(mut self, name: &str) -> Self {
+        if !self.option_map.contains_key(name) {
+            panic!("'{}' is not a registered option name", name);
+        }
+        self.config_option = Some(name.to_string());
+        self
+    }
+
+    // Returns the parser's config, loading it if it hasn't already been loaded. A command
+    // parser's config starts from its parent's, then merges the command's own config files,
+    // then the files named by its config option and any config option inherited as a global.
+    pub(crate) fn load_config(&mut self) -> Result<Rc<Config>, Error> {
+        if let Some(config) = &self.config {
+            return Ok(config.clone());
+        }
+
+        let mut config = match &self.parent_config {
+            Some(parent_config) => Config::clone(parent_config),
+            None => Config::default(),
+        };
+        for path in &self.config_files {
+            if path.exists() {
+                config.merge_file(path)?;
+            }
+        }
+
+        self.apply_config_option_env()?;
+        for name in self.config_option.iter().chain(&self.inherited_config_option) {
+            for path in self.values_os(name) {
+                config.merge_file(Path::new(&path))?;
+            }
+        }
+
+        let config = Rc::new(config);
+        self.config = Some(config.clone());
+        Ok(config)
+    }
+
+    // Fills in the config option's value from its environment variable if it was not found on
+    // the command line. This has to happen before the config is loaded, so the config option
+    // does not wait for the other fallbacks.
+    fn apply_config_option_env(&mut self) -> Result<(), Error> {
+        let index = match self.config_option.as_ref().and_then(|name| self.option_map.get(name)) {
+            Some(index) => *index,
+            None => return Ok(()),
+        };
+        let opt = &self.options[index];
+        if !opt.values.is_empty() || opt.inherited {
+            return Ok(());
+        }
+        if let Some(value) = opt.env.as_ref().and_then(|var| self.env_var(var)) {
+            self.options[index].push(OsString::from(value))?;
+        }
+        Ok(())
+    }
+}
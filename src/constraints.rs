@@ -0,0 +1,187 @@
+// Support for declarative constraints between flags and options.
+
+use crate::ArgParser;
+use crate::Error;
+use crate::ErrorKind;
+
+
+// A constraint on a group of flags and options. Names are stored as the first name
+// registered for each flag or option.
+pub(crate) enum Constraint {
+    Conflicts(Vec<String>),
+    AtLeastOne(Vec<String>),
+    ExactlyOne(Vec<String>),
+    Requires(String, String),
+}
+
+
+impl ArgParser {
+    /// Registers a group of mutually exclusive flags and options. Parsing will fail with a
+    /// `Conflict` error if more than one of them is found. If any of them is found on the
+    /// command line, environment variable and config file values for the others are ignored.
+    /// (This function will panic if any name is not a registered flag or option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .flag("json")
+    ///     .flag("csv")
+    ///     .conflicts(&["json", "csv"]);
+    /// ```
+    pub fn conflicts(mut self, names: &[&str]) -> Self {
+        let names = self.constraint_names(names);
+        self.constraints.push(Constraint::Conflicts(names));
+        self
+    }
+
+    /// Registers a group of flags and options of which at least one must be found. Parsing
+    /// will fail with a `MissingRequired` error if none of them are found.
+    /// (This function will panic if any name is not a registered flag or option name.)
+    pub fn at_least_one(mut self, names: &[&str]) -> Self {
+        let names = self.constraint_names(names);
+        self.constraints.push(Constraint::AtLeastOne(names));
+        self
+    }
+
+    /// Registers a group of flags and options of which exactly one must be found. Parsing
+    /// will fail with a `MissingRequired` error if none of them are found or with a
+    /// `Conflict` error if more than one of them is found. If any of them is found on the
+    /// command line, environment variable and config file values for the others are ignored.
+    /// (This function will panic if any name is not a registered flag or option name.)
+    pub fn exactly_one(mut self, names: &[&str]) -> Self {
+        let names = self.constraint_names(names);
+        self.constraints.push(Constraint::ExactlyOne(names));
+        self
+    }
+
+    /// Registers a dependency between two flags or options. Parsing will fail with a
+    /// `MissingRequired` error if `name` is found but `required` is not.
+    /// (This function will panic if either name is not a registered flag or option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("user", "")
+    ///     .option("password", "")
+    ///     .requires("user", "password");
+    /// ```
+    pub fn requires(mut self, name: &str, required: &str) -> Self {
+        let name = self.constraint_name(name);
+        let required = self.constraint_name(required);
+        self.constraints.push(Constraint::Requires(name, required));
+        self
+    }
+
+    // Resolves a list of names to their flags' and options' first registered names, dropping
+    // any aliases of the same flag or option.
+    fn constraint_names(&self, names: &[&str]) -> Vec<String> {
+        let mut resolved = Vec::new();
+        for name in names {
+            let name = self.constraint_name(name);
+            if !resolved.contains(&name) {
+                resolved.push(name);
+            }
+        }
+        resolved
+    }
+
+    fn constraint_name(&self, name: &str) -> String {
+        if let Some(index) = self.flag_map.get(name) {
+            return self.flags[*index].names[0].clone();
+        }
+        if let Some(index) = self.option_map.get(name) {
+            return self.options[*index].names[0].clone();
+        }
+        panic!("'{}' is not a registered flag or option name", name);
+    }
+
+    // Returns the name of a registered flag or option in command line form.
+    fn constraint_display_name(&self, name: &str) -> String {
+        if let Some(index) = self.flag_map.get(name) {
+            return crate::display_name(&self.flags[*index].names);
+        }
+        if let Some(index) = self.option_map.get(name) {
+            return crate::display_name(&self.options[*index].names);
+        }
+        name.to_string()
+    }
+
+    // Formats a list of names as e.g. `--foo, --bar, or --baz`.
+    fn constraint_list(&self, names: &[String], conjunction: &str) -> String {
+        let names: Vec<String> = names.iter()
+            .map(|name| self.constraint_display_name(name))
+            .collect();
+        match names.len() {
+            0 => String::new(),
+            1 => names[0].clone(),
+            2 => format!("{} {} {}", names[0], conjunction, names[1]),
+            n => format!("{}, {} {}", names[..n - 1].join(", "), conjunction, names[n - 1]),
+        }
+    }
+
+    // Returns the names of flags and options whose fallback values should be ignored because
+    // another member of a conflicting group was found on the command line. This must be called
+    // before the fallback values are applied.
+    pub(crate) fn overridden_fallbacks(&self) -> Vec<String> {
+        let mut overridden = Vec::new();
+        for constraint in &self.constraints {
+            if let Constraint::Conflicts(names) | Constraint::ExactlyOne(names) = constraint {
+                if names.iter().any(|name| self.found(name)) {
+                    overridden.extend(names.iter().filter(|name| !self.found(name)).cloned());
+                }
+            }
+        }
+        overridden
+    }
+
+    // Returns the names in a constraint group which were found on the command line.
+    fn found_names(&self, names: &[String]) -> Vec<String> {
+        names.iter().filter(|name| self.found(name)).cloned().collect()
+    }
+
+    // Checks the registered constraints against the flags and options found.
+    pub(crate) fn check_constraints(&self) -> Result<(), Error> {
+        for constraint in &self.constraints {
+            match constraint {
+                Constraint::Conflicts(names) => {
+                    let found = self.found_names(names);
+                    if found.len() > 1 {
+                        return Err(Error::new(ErrorKind::Conflict, format!(
+                            "{} cannot be used together", self.constraint_list(&found, "and")
+                        )));
+                    }
+                }
+                Constraint::AtLeastOne(names) => {
+                    if !names.iter().any(|name| self.found(name)) {
+                        return Err(Error::new(ErrorKind::MissingRequired, format!(
+                            "at least one of {} is required", self.constraint_list(names, "or")
+                        )));
+                    }
+                }
+                Constraint::ExactlyOne(names) => {
+                    let found = self.found_names(names);
+                    if found.is_empty() {
+                        return Err(Error::new(ErrorKind::MissingRequired, format!(
+                            "one of {} is required", self.constraint_list(names, "or")
+                        )));
+                    }
+                    if found.len() > 1 {
+                        return Err(Error::new(ErrorKind::Conflict, format!(
+                            "{} cannot be used together", self.constraint_list(&found, "and")
+                        )));
+                    }
+                }
+                Constraint::Requires(name, required) => {
+                    if self.found(name) && !self.found(required) {
+                        return Err(Error::new(ErrorKind::MissingRequired, format!(
+                            "{} requires {}",
+                            self.constraint_display_name(name),
+                            self.constraint_display_name(required)
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+// Support for global flags and options which are inherited by command parsers.
+
+use crate::ArgParser;
+use crate::Flag;
+use crate::Opt;
+
+
+// The global flags and options a command's parser inherits from the parsers above it. The
+// completion script and man page generators use this to include inherited globals, as they
+// walk the registered commands without parsing.
+#[derive(Default)]
+pub(crate) struct Inherited<'a> {
+    options: Vec<&'a Opt>,
+    flags: Vec<&'a Flag>,
+}
+
+
+impl ArgParser {
+    /// Marks a registered flag or option as global. Global flags and options are inherited by
+    /// the parser's commands, including nested commands, so `app build --verbose` is parsed
+    /// in the same way as `app --verbose build`. Their values can be read from the parser
+    /// itself and from the command parsers. A command parser which registers a flag or option
+    /// with any of the same names does not inherit it. Inherited flags and options are listed
+    /// in the commands' helptext, completion scripts, and man pages. Environment variable and
+    /// config file values for global flags and options are looked up by the parser which
+    /// registered them.
+    /// (This function will panic if `name` is not a registered flag or option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .flag("verbose v")
+    ///     .global("verbose")
+    ///     .command("build", ArgParser::new());
+    ///
+    /// parser.parse_vec(vec!["build", "--verbose"]).unwrap();
+    /// assert!(parser.found("verbose"));
+    /// assert!(parser.cmd_parser.unwrap().found("verbose"));
+    /// ```
+    pub fn global(mut self, name: &str) -> Self {
+        if let Some(index) = self.flag_map.get(name) {
+            self.flags[*index].global = true;
+        } else if let Some(index) = self.option_map.get(name) {
+            self.options[*index].global = true;
+        } else {
+            panic!("'{}' is not a registered flag or option name", name);
+        }
+        self
+    }
+
+    // Registers copies of the parser's global flags and options, along with any values found
+    // so far, on a command's parser.
+    pub(crate) fn inherit_globals(&self, cmd_parser: &mut ArgParser) {
+        for opt in self.options.iter().filter(|opt| opt.global) {
+            if cmd_parser.shadows(&opt.names) {
+                continue;
+            }
+            cmd_parser.options.push(Opt { inherited: true, ..opt.clone() });
+            let index = cmd_parser.options.len() - 1;
+            for alias in &opt.names {
+                cmd_parser.option_map.insert(alias.clone(), index);
+            }
+        }
+        for flag in self.flags.iter().filter(|flag| flag.global) {
+            if cmd_parser.shadows(&flag.names) {
+                continue;
+            }
+            cmd_parser.flags.push(Flag { inherited: true, ..flag.clone() });
+            let index = cmd_parser.flags.len() - 1;
+            for alias in &flag.names {
+                cmd_parser.flag_map.insert(alias.clone(), index);
+            }
+        }
+    }
+
+    // Copies the values of the parser's global flags and options back from a command's parser
+    // once it has finished parsing.
+    pub(crate) fn collect_globals(&mut self, cmd_parser: &ArgParser) {
+        for opt in self.options.iter_mut().filter(|opt| opt.global) {
+            if let Some(index) = cmd_parser.inherited_option(&opt.names) {
+                opt.values = cmd_parser.options[index].values.clone();
+                opt.occurrences = cmd_parser.options[index].occurrences.clone();
+            }
+        }
+        // A global config option may have been given after the command name, so the config
+        // has to be reloaded.
+        if cmd_parser.inherited_config_option.is_some() {
+            self.config = None;
+        }
+        for flag in self.flags.iter_mut().filter(|flag| flag.global) {
+            if let Some(index) = cmd_parser.inherited_flag(&flag.names) {
+                flag.count = cmd_parser.flags[index].count;
+                flag.last_seen = cmd_parser.flags[index].last_seen;
+            }
+        }
+    }
+
+    // Copies the final values of the parser's global flags and options, including any
+    // fallback values, down to the commands found.
+    pub(crate) fn share_globals(&mut self) {
+        let mut cmd_parser = match self.cmd_parser.take() {
+            Some(cmd_parser) => cmd_parser,
+            None => return,
+        };
+        for opt in self.options.iter().filter(|opt| opt.global) {
+            if let Some(index) = cmd_parser.inherited_option(&opt.names) {
+                cmd_parser.options[index].values = opt.values.clone();
+                cmd_parser.options[index].occurrences = opt.occurrences.clone();
+            }
+        }
+        for flag in self.flags.iter().filter(|flag| flag.global) {
+            if let Some(index) = cmd_parser.inherited_flag(&flag.names) {
+                cmd_parser.flags[index].count = flag.count;
+                cmd_parser.flags[index].last_seen = flag.last_seen;
+            }
+        }
+        cmd_parser.share_globals();
+        self.cmd_parser = Some(cmd_parser);
+    }
+
+    // Returns the index of the parser's inherited copy of the global option with the given
+    // names, if any.
+    fn inherited_option(&self, names: &[String]) -> Option<usize> {
+        let index = *self.option_map.get(names.first()?)?;
+        let opt = &self.options[index];
+        if opt.inherited && opt.names == names { Some(index) } else { None }
+    }
+
+    // Returns the index of the parser's inherited copy of the global flag with the given
+    // names, if any.
+    fn inherited_flag(&self, names: &[String]) -> Option<usize> {
+        let index = *self.flag_map.get(names.first()?)?;
+        let flag = &self.flags[index];
+        if flag.inherited && flag.names == names { Some(index) } else { None }
+    }
+
+    // Returns true if the parser has an inherited copy of the global option with the given
+    // names.
+    pub(crate) fn inherits(&self, names: &[String]) -> bool {
+        self.inherited_option(names).is_some()
+    }
+
+    // Returns true if the parser registers any of the names of an inherited flag or option.
+    fn shadows(&self, names: &[String]) -> bool {
+        names.iter().any(|name| self.is_registered(name))
+    }
+
+    // Returns the parser's options followed by any inherited global options it does not
+    // shadow.
+    pub(crate) fn options_with<'a>(&'a self, inherited: &Inherited<'a>) -> Vec<&'a Opt> {
+        let mut options: Vec<&Opt> = self.options.iter().collect();
+        options.extend(inherited.options.iter().filter(|opt| !self.shadows(&opt.names)));
+        options
+    }
+
+    // Returns the parser's flags followed by any inherited global flags it does not shadow.
+    pub(crate) fn flags_with<'a>(&'a self, inherited: &Inherited<'a>) -> Vec<&'a Flag> {
+        let mut flags: Vec<&Flag> = self.flags.iter().collect();
+        flags.extend(inherited.flags.iter().filter(|flag| !self.shadows(&flag.names)));
+        flags
+    }
+
+    // Returns the global flags and options inherited by the parser's commands.
+    pub(crate) fn inherited_by_commands<'a>(&'a self, inherited: &Inherited<'a>) -> Inherited<'a> {
+        Inherited {
+            options: self.options_with(inherited).into_iter().filter(|opt| opt.global).collect(),
+            flags: self.flags_with(inherited).into_iter().filter(|flag| flag.global).collect(),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// Support for auto-generated helptext.
+
+use crate::ArgParser;
+
+
+// Descriptions are wrapped to the terminal width, read from the `COLUMNS` environment
+// variable, falling back to this default.
+const DEFAULT_WIDTH: usize = 80;
+
+// Descriptions are aligned in a column which starts at most this many characters in.
+const MAX_INDENT: usize = 30;
+
+
+impl ArgParser {
+    // Returns true if the parser supports an automatic `--help` flag.
+    pub(crate) fn has_helptext(&self) -> bool {
+        self.helptext.is_some() || self.generate_helptext
+    }
+
+    // Returns the application name to use in usage lines.
+    pub(crate) fn app_name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        std::env::args_os()
+            .next()
+            .as_ref()
+            .and_then(|arg0| std::path::Path::new(arg0).file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Returns the parser's helptext. If auto-generated helptext is enabled this is built from
+    /// the registered flags, options, and commands, otherwise it is the string supplied via
+    /// `.helptext()`.
+    pub fn render_helptext(&self) -> String {
+        if !self.generate_helptext {
+            return self.helptext.clone().unwrap_or_default();
+        }
+
+        let width = terminal_width();
+        let mut sections = Vec::new();
+
+        if let Some(header) = &self.helptext {
+            sections.push(header.trim().to_string());
+        }
+
+        let mut usage = format!("Usage: {} [options]", self.app_name());
+        if !self.commands.is_empty() {
+            usage.push_str(" [command]");
+        }
+        usage.push(' ');
+        usage.push_str(&self.positional_usage());
+        sections.push(usage);
+
+        let arguments = self.positional_rows();
+        if !arguments.is_empty() {
+            sections.push(format!("Arguments:\n{}", format_rows(&arguments, width)));
+        }
+
+        let options = self.option_rows();
+        if !options.is_empty() {
+            sections.push(format!("Options:\n{}", format_rows(&options, width)));
+        }
+
+        let commands = self.command_rows();
+        if !commands.is_empty() {
+            sections.push(format!("Commands:\n{}", format_rows(&commands, width)));
+        }
+
+        if let Some(footer) = &self.footer {
+            sections.push(footer.trim().to_string());
+        }
+
+        sections.join("\n\n")
+    }
+
+    // Returns a (names, description) row for each flag and option in registration order,
+    // followed by rows for the automatic `--help` and `--version` flags.
+    fn option_rows(&self) -> Vec<(String, String)> {
+        let mut rows = Vec::new();
+
+        for opt in &self.options {
+            let value_name = opt.value_name.as_deref().unwrap_or("arg");
+            let names = match opt.implicit_value {
+                Some(_) => format!("{}[=<{}>]", format_names(&opt.names), value_name),
+                None => {
+                    let placeholder = format!("<{}>", value_name);
+                    format!("{} {}", format_names(&opt.names), opt.value_usage(&placeholder))
+                }
+            };
+            let mut description = opt.description.clone().unwrap_or_default();
+            if opt.required {
+                append_note(&mut description, "[required]");
+            }
+            if !opt.choices.is_empty() {
+                append_note(&mut description, &format!("[choices: {}]", opt.choices.join(", ")));
+            }
+            if let Some(var) = &opt.env {
+                append_note(&mut description, &format!("[env: {}]", var));
+            }
+            if !opt.default.is_empty() {
+                append_note(&mut description, &format!("[default: {}]", opt.default));
+            }
+            rows.push((names, description));
+        }
+
+        for flag in &self.flags {
+            let mut description = flag.description.clone().unwrap_or_default();
+            if let Some(var) = &flag.env {
+                append_note(&mut description, &format!("[env: {}]", var));
+            }
+            rows.push((format_names(&flag.display_names()), description));
+        }
+
+        if self.has_helptext() && !self.is_registered("help") {
+            let names = if self.is_registered("h") { "--help" } else { "-h, --help" };
+            rows.push((names.to_string(), "Print this helptext and exit.".to_string()));
+        }
+
+        if self.version.is_some() && !self.is_registered("version") {
+            let names = if self.is_registered("v") { "--version" } else { "-v, --version" };
+            rows.push((names.to_string(), "Print the version number and exit.".to_string()));
+        }
+
+        rows
+    }
+
+    // Returns a (name, description) row for each declared positional with a description.
+    fn positional_rows(&self) -> Vec<(String, String)> {
+        self.positionals.iter()
+            .filter_map(|positional| {
+                let description = positional.description.clone()?;
+                Some((positional.name.clone(), description))
+            })
+            .collect()
+    }
+
+    // Returns a (names, description) row for each command in registration order.
+    fn command_rows(&self) -> Vec<(String, String)> {
+        let mut rows = Vec::new();
+
+        for cmd_parser in &self.commands {
+            let names = cmd_parser.aliases.join(", ");
+            let description = cmd_parser.description.clone().unwrap_or_default();
+            rows.push((names, description));
+        }
+
+        if self.cmd_help && !self.commands.is_empty() {
+            rows.push(("help <command>".to_string(), "Print a command's helptext.".to_string()));
+        }
+
+        rows
+    }
+
+    // Returns true if `name` is a registered flag or option name.
+    pub(crate) fn is_registered(&self, name: &str) -> bool {
+        self.flag_map.contains_key(name) || self.option_map.contains_key(name)
+    }
+
+    // Filters a list of automatic flag names, removing any registered by the user.
+    pub(crate) fn unregistered(&self, names: &[&str]) -> Vec<String> {
+        names.iter()
+            .filter(|name| !self.is_registered(name))
+            .map(|name| name.to_string())
+            .collect()
+    }
+}
+
+
+// Appends a bracketed note like `[default: foo]` to a description.
+fn append_note(description: &mut String, note: &str) {
+    if !description.is_empty() {
+        description.push(' ');
+    }
+    description.push_str(note);
+}
+
+
+// Formats a list of aliases with single-character shortcuts first, e.g. `-f, --foo`.
+fn format_names(names: &[String]) -> String {
+    let mut shortcuts: Vec<String> = names.iter()
+        .filter(|name| name.chars().count() == 1)
+        .map(|name| format!("-{}", name))
+        .collect();
+    let longforms: Vec<String> = names.iter()
+        .filter(|name| name.chars().count() > 1)
+        .map(|name| format!("--{}", name))
+        .collect();
+    shortcuts.extend(longforms);
+    shortcuts.join(", ")
+}
+
+
+// Formats a list of (names, description) rows as an indented two-column table.
+fn format_rows(rows: &[(String, String)], width: usize) -> String {
+    let name_width = rows.iter().map(|(names, _)| names.chars().count()).max().unwrap_or(0);
+    let indent = (name_width + 6).min(MAX_INDENT);
+    let desc_width = width.saturating_sub(indent).max(20);
+
+    let mut lines = Vec::new();
+    for (names, description) in rows {
+        let mut line = format!("  {}", names);
+        let wrapped = wrap(description, desc_width);
+        let mut wrapped = wrapped.iter();
+
+        if let Some(first) = wrapped.next() {
+            if line.chars().count() + 2 <= indent {
+                let padding = indent - line.chars().count();
+                line.push_str(&" ".repeat(padding));
+                line.push_str(first);
+            } else {
+                lines.push(line);
+                line = format!("{}{}", " ".repeat(indent), first);
+            }
+        }
+        lines.push(line);
+
+        for rest in wrapped {
+            lines.push(format!("{}{}", " ".repeat(indent), rest));
+        }
+    }
+
+    lines.join("\n")
+}
+
+
+// Splits `text` into lines of at most `width` characters, breaking at whitespace.
+// Words longer than `width` are left unbroken.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+
+// Returns the terminal width as specified by the `COLUMNS` environment variable.
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|width| *width > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
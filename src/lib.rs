@@ -3,11 +3,32 @@
 //! ## Features
 //!
 //! * Long-form boolean flags with single-character shortcuts: `--flag`, `-f`.
+//! * Optional `--no-flag` negations for boolean flags.
 //! * Long-form string-valued options with single-character shortcuts: `--option <arg>`, `-o <arg>`.
 //! * Condensed short-form options: `-abc <arg> <arg>`.
 //! * Automatic `--help` and `--version` flags.
-//! * Support for multivalued options.
+//! * Optional auto-generated helptext built from flag, option, and command descriptions.
+//! * Support for multivalued options, including options which take a fixed or variable
+//!   number of values each time they are found.
+//! * Delimiter-split option values: `--tags a,b,c`.
 //! * Support for git-style command interfaces with arbitrarily-nested commands.
+//! * Global flags and options which are accepted at any depth of the command tree.
+//! * Command handlers which can capture state, fail, and share a user context.
+//! * Completion script generation for bash, zsh, and fish.
+//! * Man page generation in roff format.
+//! * Optional support for arguments which are not valid unicode.
+//! * Required options and limits on the number of positional arguments.
+//! * Named positional arguments, which can be single, optional, or variadic.
+//! * Mutually exclusive, at-least-one, exactly-one, and requires constraints.
+//! * Environment variable fallbacks for options and flags.
+//! * Restricted value choices and custom validators for options.
+//! * Layered config files for option and flag defaults.
+//! * Optional support for `@file` response files.
+//! * Optional derive macros for declaring an interface as a struct (requires the `derive`
+//!   feature).
+//! * Typed accessors for option values and positional arguments.
+//! * "Did you mean" suggestions for mistyped flag, option, and command names.
+//! * Optional support for abbreviated long-form flag, option, and command names.
 //!
 //! ## Example
 //!
@@ -34,48 +55,304 @@
 //! }
 //! ```
 
+use std::any::Any;
 use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::error;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
 
+mod abbreviations;
+mod cli;
+mod completion;
+mod config;
+mod constraints;
+mod globals;
+mod help;
+mod manpage;
+mod positionals;
+mod response;
+mod suggest;
 
-/// Error types returned by the library.
-#[derive(Debug)]
-pub enum Error {
+pub use cli::{Cli, Commands};
+pub use completion::Shell;
+pub use manpage::ManPage;
+
+#[cfg(feature = "derive")]
+pub use arguably_derive::{Cli, Commands};
+
+
+/// The kinds of error returned by the library. New kinds may be added in future releases,
+/// so matches on `ErrorKind` should include a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
     /// Returned when the parser detects an unregistered flag, option, or command name.
-    InvalidName(String),
+    InvalidName,
 
     /// Returned when the parser detects an option with a missing value.
-    MissingValue(String),
+    MissingValue,
 
     /// Returned when the parser detects a help command with a missing argument.
     MissingHelpArg,
 
-    /// Returned when the command line arguments are not valid unicode strings.
+    /// Returned when a command line argument is not a valid unicode string.
     InvalidUnicode,
+
+    /// Returned when an option value or positional argument cannot be converted to the
+    /// requested type.
+    InvalidValue,
+
+    /// Returned when an option value is not one of the option's registered choices.
+    InvalidChoice,
+
+    /// Returned when an option value is rejected by the option's registered validator.
+    ValidationFailed,
+
+    /// Returned when a required option is missing.
+    MissingRequired,
+
+    /// Returned when the parser finds flags or options which cannot be used together.
+    Conflict,
+
+    /// Returned when the parser finds fewer positional arguments than the registered minimum.
+    TooFewArgs,
+
+    /// Returned when the parser finds more positional arguments than the registered maximum.
+    TooManyArgs,
+
+    /// Returned when a config file cannot be read or parsed.
+    InvalidConfig,
+
+    /// Returned when a response file cannot be read or parsed.
+    InvalidResponseFile,
+
+    /// Returned when a command handler fails. The handler's error is available via
+    /// `.source()`.
+    HandlerFailed,
+
+    /// Returned when the parser detects a `--help` flag or `help` command and exiting on help
+    /// has been disabled. The error's message is the helptext.
+    HelpRequested,
+
+    /// Returned when the parser detects a `--version` flag and exiting on help has been
+    /// disabled. The error's message is the version string.
+    VersionRequested,
+
+    /// Returned when the parser detects a `completion` command and exiting on help has been
+    /// disabled. The error's message is the completion script.
+    CompletionRequested,
+}
+
+
+/// The error type returned by the library.
+///
+/// ```
+/// # use arguably::{ArgParser, ErrorKind};
+/// let mut parser = ArgParser::new()
+///     .flag("verbose");
+///
+/// let err = parser.parse_vec(vec!["--verbsoe"]).unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::InvalidName);
+/// assert_eq!(err.token(), Some("--verbsoe"));
+/// assert_eq!(err.index(), Some(1));
+/// assert_eq!(err.suggestions(), ["--verbose"]);
+/// assert_eq!(err.exit_code(), 64);
+/// ```
+#[derive(Debug)]
+pub struct Error {
+    inner: Box<ErrorInner>,
+}
+
+
+// The error's fields are boxed to keep `Result<T, Error>` small.
+#[derive(Debug)]
+struct ErrorInner {
+    kind: ErrorKind,
+    message: String,
+    token: Option<String>,
+    index: Option<usize>,
+    command_path: Vec<String>,
+    suggestions: Vec<String>,
+    source: Option<Box<dyn error::Error>>,
 }
 
 
-impl error::Error for Error {}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.inner.source.as_deref()
+    }
+}
 
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Error::InvalidName(msg) =>  write!(f, "Error: {}", msg),
-            Error::MissingValue(msg) =>  write!(f, "Error: {}", msg),
-            Error::MissingHelpArg => write!(f, "Error: missing argument for the help command"),
-            Error::InvalidUnicode => write!(f, "Error: arguments are not valid unicode strings"),
+        if self.is_exit_request() {
+            write!(f, "{}", self.inner.message)
+        } else {
+            write!(f, "Error: {}", self.inner.message)
         }
     }
 }
 
 
 impl Error {
-    /// Prints an error message to `stderr` and exits with a non-zero status code.
+    /// Creates a new error of the specified kind.
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Error {
+        Error {
+            inner: Box::new(ErrorInner {
+                kind,
+                message: message.into(),
+                token: None,
+                index: None,
+                command_path: Vec::new(),
+                suggestions: Vec::new(),
+                source: None,
+            }),
+        }
+    }
+
+    /// Returns the kind of error.
+    pub fn kind(&self) -> ErrorKind {
+        self.inner.kind
+    }
+
+    /// Returns the error message. For help, version, and completion requests, this is the
+    /// requested text.
+    pub fn message(&self) -> &str {
+        &self.inner.message
+    }
+
+    /// Returns the command line argument which caused the error, if any, e.g. an unrecognised
+    /// flag name or an invalid option value.
+    pub fn token(&self) -> Option<&str> {
+        self.inner.token.as_deref()
+    }
+
+    /// Returns the position of the argument which caused the error in the argument list,
+    /// starting from 1, if known. Arguments read from response files report the position of
+    /// the response file argument.
+    pub fn index(&self) -> Option<usize> {
+        self.inner.index
+    }
+
+    /// Returns the canonical names of the commands leading to the parser which detected the
+    /// error. The list is empty if the error was detected by the root parser.
+    pub fn command_path(&self) -> &[String] {
+        &self.inner.command_path
+    }
+
+    /// Returns the registered names closest to an unrecognised flag, option, or command name,
+    /// nearest first, in command line form, e.g. `--verbose`. The list may be empty.
+    pub fn suggestions(&self) -> &[String] {
+        &self.inner.suggestions
+    }
+
+    /// Returns a suggested exit status code for the error: `0` for help, version, and
+    /// completion requests, `78` (`EX_CONFIG`) for config file errors, `1` for handler
+    /// failures, and `64` (`EX_USAGE`) for all other errors.
+    pub fn exit_code(&self) -> i32 {
+        match self.inner.kind {
+            ErrorKind::HelpRequested
+            | ErrorKind::VersionRequested
+            | ErrorKind::CompletionRequested => 0,
+            ErrorKind::InvalidConfig => 78,
+            ErrorKind::HandlerFailed => 1,
+            _ => 64,
+        }
+    }
+
+    /// Prints an error message to `stderr` and exits with the error's suggested status code.
+    /// For help, version, and completion requests, prints the requested text to `stdout` and
+    /// exits with a zero status code.
     pub fn exit(self) -> ! {
-        eprintln!("{}.", self);
-        std::process::exit(1);
+        if self.is_exit_request() {
+            println!("{}", self.inner.message);
+        } else {
+            eprintln!("{}.", self);
+        }
+        std::process::exit(self.exit_code());
+    }
+
+    // Returns true if the error is a request to print text and exit successfully.
+    fn is_exit_request(&self) -> bool {
+        self.exit_code() == 0
+    }
+
+    // Records the argument which caused the error unless one has already been recorded.
+    fn with_token(mut self, token: &str) -> Error {
+        if self.inner.token.is_none() {
+            self.inner.token = Some(token.to_string());
+        }
+        self
+    }
+
+    // Records the position of the argument which caused the error unless one has already
+    // been recorded.
+    fn at(mut self, index: usize) -> Error {
+        if self.inner.index.is_none() {
+            self.inner.index = Some(index);
+        }
+        self
+    }
+
+    // Converts an error returned by a command handler. Errors returned by the library are
+    // passed through unchanged.
+    fn from_handler(err: Box<dyn error::Error>) -> Error {
+        match err.downcast::<Error>() {
+            Ok(err) => *err,
+            Err(err) => {
+                let mut wrapped = Error::new(ErrorKind::HandlerFailed, err.to_string());
+                wrapped.inner.source = Some(err);
+                wrapped
+            }
+        }
+    }
+
+    // Prepends a command name to the error's command path.
+    fn in_command(mut self, name: &str) -> Error {
+        self.inner.command_path.insert(0, name.to_string());
+        self
+    }
+}
+
+
+// A function for looking up environment variables.
+type EnvSource = dyn Fn(&str) -> Option<String>;
+
+// A function for validating option values.
+type Validator = dyn Fn(&str) -> Result<(), String>;
+
+// A command handler. Receives the command name, the command's parser, and the user context.
+type Handler = dyn FnMut(&str, &ArgParser, &mut dyn Any) -> Result<(), Box<dyn error::Error>>;
+
+
+/// The number of values an option accepts each time it is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// Exactly this number of values, e.g. `--point <x> <y>`.
+    Exactly(usize),
+
+    /// At least this number of values. Further values are consumed up to the next flag or
+    /// option.
+    AtLeast(usize),
+
+    /// Any number of values, including none. Values are consumed up to the next flag or
+    /// option.
+    Variadic,
+}
+
+
+impl Arity {
+    // Returns the minimum and maximum number of values.
+    fn bounds(self) -> (usize, Option<usize>) {
+        match self {
+            Arity::Exactly(n) => (n, Some(n)),
+            Arity::AtLeast(n) => (n, None),
+            Arity::Variadic => (0, None),
+        }
     }
 }
 
@@ -92,14 +369,36 @@ impl Error {
 /// ```
 pub struct ArgParser {
     helptext: Option<String>,
+    footer: Option<String>,
+    generate_helptext: bool,
     version: Option<String>,
+    name: Option<String>,
+    aliases: Vec<String>,
+    description: Option<String>,
     options: Vec<Opt>,
     option_map: HashMap<String, usize>,
     flags: Vec<Flag>,
     flag_map: HashMap<String, usize>,
     commands: Vec<ArgParser>,
     command_map: HashMap<String, usize>,
-    callback: Option<fn(&str, &ArgParser)>,
+    callback: Option<Box<Handler>>,
+    cmd_completion: bool,
+    allow_invalid_unicode: bool,
+    allow_abbreviations: bool,
+    exit_on_help: bool,
+    min_args: usize,
+    max_args: Option<usize>,
+    env_source: Option<Rc<EnvSource>>,
+    config_files: Vec<PathBuf>,
+    config_option: Option<String>,
+    inherited_config_option: Option<String>,
+    config: Option<Rc<config::Config>>,
+    parent_config: Option<Rc<config::Config>>,
+    config_section: String,
+    response_file_prefix: Option<char>,
+    constraints: Vec<constraints::Constraint>,
+    positionals: Vec<positionals::Positional>,
+    args_os: Vec<OsString>,
 
     /// Stores positional arguments.
     pub args: Vec<String>,
@@ -120,7 +419,12 @@ impl ArgParser {
     pub fn new() -> ArgParser {
         ArgParser {
             helptext: None,
+            footer: None,
+            generate_helptext: false,
             version: None,
+            name: None,
+            aliases: Vec::new(),
+            description: None,
             args: Vec::new(),
             options: Vec::new(),
             option_map: HashMap::new(),
@@ -129,6 +433,23 @@ impl ArgParser {
             commands: Vec::new(),
             command_map: HashMap::new(),
             callback: None,
+            cmd_completion: false,
+            allow_invalid_unicode: false,
+            allow_abbreviations: false,
+            exit_on_help: true,
+            min_args: 0,
+            max_args: None,
+            env_source: None,
+            config_files: Vec::new(),
+            config_option: None,
+            inherited_config_option: None,
+            config: None,
+            parent_config: None,
+            config_section: String::new(),
+            response_file_prefix: None,
+            constraints: Vec::new(),
+            positionals: Vec::new(),
+            args_os: Vec::new(),
             cmd_name: None,
             cmd_parser: None,
             cmd_help: false,
@@ -149,6 +470,303 @@ impl ArgParser {
         self
     }
 
+    /// Sets the parser's helptext footer. The footer is printed at the end of the
+    /// auto-generated helptext.
+    pub fn footer<S>(mut self, text: S) -> Self where S: Into<String> {
+        self.footer = Some(text.into());
+        self
+    }
+
+    /// This boolean switch toggles support for auto-generated helptext. If enabled, the
+    /// `--help` flag prints a usage line followed by lists of the parser's registered options
+    /// and commands, built from the descriptions supplied via `.describe()`. Any string
+    /// supplied via `.helptext()` is printed as a header. The value defaults to `false`.
+    /// Enabling the feature on a parser also enables it on that parser's commands.
+    ///
+    /// Descriptions are wrapped to the width given by the `COLUMNS` environment variable.
+    /// The terminal itself is not queried, so if `COLUMNS` is unset or invalid, as it usually
+    /// is when the shell doesn't export it, descriptions are wrapped to 80 columns.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .name("appname")
+    ///     .generate_helptext(true)
+    ///     .option("bar b", "default")
+    ///     .describe("bar", "Sets the value of bar.")
+    ///     .value_name("bar", "value");
+    /// ```
+    pub fn generate_helptext(mut self, enable: bool) -> Self {
+        self.generate_helptext = enable;
+        self
+    }
+
+    /// Sets the application name used in the auto-generated usage line. Defaults to the
+    /// filename of the running executable.
+    pub fn name<S>(mut self, text: S) -> Self where S: Into<String> {
+        self.name = Some(text.into());
+        self
+    }
+
+    /// Sets the description of a registered flag, option, or command for use in the
+    /// auto-generated helptext. Any of the registered aliases or shortcuts can be used
+    /// for the `name` parameter.
+    /// (This function will panic if `name` is not a registered flag, option, or command name.)
+    pub fn describe(mut self, name: &str, text: &str) -> Self {
+        if let Some(index) = self.flag_map.get(name) {
+            self.flags[*index].description = Some(text.to_string());
+        } else if let Some(index) = self.option_map.get(name) {
+            self.options[*index].description = Some(text.to_string());
+        } else if let Some(index) = self.command_map.get(name) {
+            self.commands[*index].description = Some(text.to_string());
+        } else if let Some(index) = self.positional_index(name) {
+            self.positionals[index].description = Some(text.to_string());
+        } else {
+            panic!("'{}' is not a registered flag, option, command, or positional name", name);
+        }
+        self
+    }
+
+    /// Sets the placeholder name used for a registered option's value in the
+    /// auto-generated helptext, e.g. `--output <file>`. Defaults to `arg`.
+    /// (This function will panic if `name` is not a registered option name.)
+    pub fn value_name(mut self, name: &str, text: &str) -> Self {
+        if let Some(index) = self.option_map.get(name) {
+            self.options[*index].value_name = Some(text.to_string());
+            return self;
+        }
+        panic!("'{}' is not a registered option name", name);
+    }
+
+    /// Marks a registered option as required. Parsing will fail with a
+    /// `MissingRequired` error if the option is not found.
+    /// (This function will panic if `name` is not a registered option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("output o", "")
+    ///     .required("output");
+    /// ```
+    pub fn required(mut self, name: &str) -> Self {
+        if let Some(index) = self.option_map.get(name) {
+            self.options[*index].required = true;
+            return self;
+        }
+        panic!("'{}' is not a registered option name", name);
+    }
+
+    /// Restricts a registered option's values to a fixed set of choices. Parsing will fail
+    /// with an `InvalidChoice` error if the option is given any other value. The choices are
+    /// listed in the auto-generated helptext and used by generated completion scripts.
+    /// (This function will panic if `name` is not a registered option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("format f", "json")
+    ///     .choices("format", &["json", "csv", "table"]);
+    /// ```
+    pub fn choices(mut self, name: &str, choices: &[&str]) -> Self {
+        if let Some(index) = self.option_map.get(name) {
+            self.options[*index].choices = choices.iter().map(|c| c.to_string()).collect();
+            self.options[*index].ignore_case = false;
+            return self;
+        }
+        panic!("'{}' is not a registered option name", name);
+    }
+
+    /// Like `.choices()` but matches values case-insensitively. Matching values are stored
+    /// using the spelling of the registered choice.
+    /// (This function will panic if `name` is not a registered option name.)
+    pub fn choices_ignore_case(self, name: &str, choices: &[&str]) -> Self {
+        let mut parser = self.choices(name, choices);
+        let index = parser.option_map[name];
+        parser.options[index].ignore_case = true;
+        parser
+    }
+
+    /// Registers a validator function for a registered option. The function is called on
+    /// each of the option's values as it is found and should return an error message if the
+    /// value is invalid, in which case parsing will fail with a `ValidationFailed` error.
+    /// Values from environment variables and config files are also validated.
+    /// (This function will panic if `name` is not a registered option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("port p", "8080")
+    ///     .validator("port", |value| match value.parse::<u16>() {
+    ///         Ok(port) if port >= 1024 => Ok(()),
+    ///         _ => Err("expected a port number between 1024 and 65535".to_string()),
+    ///     });
+    /// ```
+    pub fn validator<F>(mut self, name: &str, f: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), String> + 'static,
+    {
+        if let Some(index) = self.option_map.get(name) {
+            self.options[*index].validator = Some(Rc::new(f));
+            return self;
+        }
+        panic!("'{}' is not a registered option name", name);
+    }
+
+    /// Sets the number of values a registered option accepts each time it is found. The
+    /// value defaults to `Arity::Exactly(1)`. The values from each occurrence are available
+    /// as a group via `.occurrences()`.
+    /// (This function will panic if `name` is not a registered option name.)
+    ///
+    /// ```
+    /// # use arguably::{ArgParser, Arity};
+    /// let mut parser = ArgParser::new()
+    ///     .option("point", "")
+    ///     .arity("point", Arity::Exactly(2))
+    ///     .option("files", "")
+    ///     .arity("files", Arity::Variadic);
+    ///
+    /// parser.parse_vec(vec!["--point", "1", "2", "--files", "a", "b"]).unwrap();
+    /// assert_eq!(parser.occurrences("point"), vec![vec!["1", "2"]]);
+    /// assert_eq!(parser.values("files"), vec!["a", "b"]);
+    /// ```
+    pub fn arity(mut self, name: &str, arity: Arity) -> Self {
+        if let Some(index) = self.option_map.get(name) {
+            self.options[*index].arity = arity;
+            return self;
+        }
+        panic!("'{}' is not a registered option name", name);
+    }
+
+    /// Sets a delimiter for a registered option's values. Each value is split on the delimiter
+    /// and the parts are stored as separate values, so `--tags a,b` and `--tags a --tags b`
+    /// produce the same list of values. A delimiter can be included in a value by escaping it
+    /// with a backslash. Values from environment variables and config files are split in the
+    /// same way. Values which are not valid unicode are not split. An option's arity counts
+    /// arguments before they are split.
+    /// (This function will panic if `name` is not a registered option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("tags", "")
+    ///     .delimiter("tags", ',');
+    ///
+    /// parser.parse_vec(vec!["--tags", "a,b", "--tags", "c\\,d"]).unwrap();
+    /// assert_eq!(parser.values("tags"), vec!["a", "b", "c,d"]);
+    /// ```
+    pub fn delimiter(mut self, name: &str, delimiter: char) -> Self {
+        if let Some(index) = self.option_map.get(name) {
+            self.options[*index].delimiter = Some(delimiter);
+            return self;
+        }
+        panic!("'{}' is not a registered option name", name);
+    }
+
+    /// Registers an automatic `--no-<name>` negation for each long-form alias of a registered
+    /// flag. A negation overrides any earlier occurrences of the flag and any value from an
+    /// environment variable or config file. Use `.enabled()` to check the flag's effective
+    /// value and `.negated()` to check if the negation was the form seen last.
+    /// (This function will panic if `name` is not a registered flag name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .flag("cache")
+    ///     .negatable("cache");
+    ///
+    /// parser.parse_vec(vec!["--cache", "--no-cache"]).unwrap();
+    /// assert!(!parser.enabled("cache"));
+    /// assert!(parser.negated("cache"));
+    /// ```
+    pub fn negatable(mut self, name: &str) -> Self {
+        if let Some(index) = self.flag_map.get(name) {
+            self.flags[*index].negatable = true;
+            return self;
+        }
+        panic!("'{}' is not a registered flag name", name);
+    }
+
+    /// Makes a registered option's value optional. If the option is found without an
+    /// attached value, e.g. `--color` or `-c`, the implicit value is used and the following
+    /// argument is left alone. A value can still be supplied using the `--color=always` or
+    /// `-c=always` forms.
+    /// (This function will panic if `name` is not a registered option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("color c", "auto")
+    ///     .implicit_value("color", "always");
+    ///
+    /// parser.parse_vec(vec!["--color", "file.txt"]).unwrap();
+    /// assert_eq!(parser.value("color"), "always");
+    /// assert_eq!(parser.args, vec!["file.txt"]);
+    /// ```
+    pub fn implicit_value(mut self, name: &str, value: &str) -> Self {
+        if let Some(index) = self.option_map.get(name) {
+            self.options[*index].implicit_value = Some(value.to_string());
+            return self;
+        }
+        panic!("'{}' is not a registered option name", name);
+    }
+
+    /// Sets the minimum number of positional arguments. Parsing will fail with a
+    /// `TooFewArgs` error if fewer arguments are found. The value defaults to `0`.
+    pub fn min_args(mut self, count: usize) -> Self {
+        self.min_args = count;
+        self
+    }
+
+    /// Sets the maximum number of positional arguments. Parsing will fail with a
+    /// `TooManyArgs` error if more arguments are found. By default there is no maximum.
+    pub fn max_args(mut self, count: usize) -> Self {
+        self.max_args = Some(count);
+        self
+    }
+
+    /// Binds a registered flag or option to an environment variable. If the flag or option
+    /// is not found on the command line, the parser will use the variable's value instead.
+    /// Boolean values for flags can be `true`/`false`, `yes`/`no`, `on`/`off`, or `1`/`0`.
+    /// (This function will panic if `name` is not a registered flag or option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("output o", "")
+    ///     .env("output", "APPNAME_OUTPUT")
+    ///     .flag("verbose")
+    ///     .env("verbose", "APPNAME_VERBOSE");
+    /// ```
+    pub fn env(mut self, name: &str, var: &str) -> Self {
+        if let Some(index) = self.flag_map.get(name) {
+            self.flags[*index].env = Some(var.to_string());
+        } else if let Some(index) = self.option_map.get(name) {
+            self.options[*index].env = Some(var.to_string());
+        } else {
+            panic!("'{}' is not a registered flag or option name", name);
+        }
+        self
+    }
+
+    /// Sets the function used to look up environment variables. Defaults to reading from
+    /// the process environment. Command parsers inherit their parent's source unless they
+    /// have their own.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("output o", "")
+    ///     .env("output", "APPNAME_OUTPUT")
+    ///     .env_source(|var| match var {
+    ///         "APPNAME_OUTPUT" => Some("out.txt".to_string()),
+    ///         _ => None,
+    ///     });
+    /// ```
+    pub fn env_source<F>(mut self, f: F) -> Self where F: Fn(&str) -> Option<String> + 'static {
+        self.env_source = Some(Rc::new(f));
+        self
+    }
+
     /// Sets the parser's version string. Supplying a version string activates support
     /// for an automatic `--version` flag, also a `-v` shortcut if not registered by another
     /// option.
@@ -174,8 +792,22 @@ impl ArgParser {
     /// ```
     pub fn option(mut self, name: &str, default: &str) -> Self {
         self.options.push(Opt {
+            names: name.split_whitespace().map(String::from).collect(),
             values: Vec::new(),
-            default: String::from(default)
+            occurrences: Vec::new(),
+            arity: Arity::Exactly(1),
+            delimiter: None,
+            global: false,
+            inherited: false,
+            default: String::from(default),
+            description: None,
+            value_name: None,
+            required: false,
+            env: None,
+            choices: Vec::new(),
+            ignore_case: false,
+            validator: None,
+            implicit_value: None,
         });
         let index = self.options.len() - 1;
         for alias in name.split_whitespace() {
@@ -194,7 +826,14 @@ impl ArgParser {
     /// ```
     pub fn flag(mut self, name: &str) -> Self {
         self.flags.push(Flag {
+            names: name.split_whitespace().map(String::from).collect(),
             count: 0,
+            description: None,
+            env: None,
+            negatable: false,
+            last_seen: None,
+            global: false,
+            inherited: false,
         });
         let index = self.flags.len() - 1;
         for alias in name.split_whitespace() {
@@ -205,7 +844,8 @@ impl ArgParser {
 
     /// Registers a new command. The `name` parameter accepts an unlimited number of
     /// space-separated aliases. The command's helptext, flags, and options can be
-    /// registered on the command's ArgParser instance.
+    /// registered on the command's ArgParser instance. A command is recognised in place of
+    /// the first positional argument, so the parser's own flags and options can precede it.
     ///
     /// ```
     /// # use arguably::ArgParser;
@@ -216,10 +856,11 @@ impl ArgParser {
     ///         .flag("cmdflag")
     ///     );
     /// ```
-    pub fn command(mut self, name: &str, cmd_parser: ArgParser) -> Self {
-        if cmd_parser.helptext.is_some() {
+    pub fn command(mut self, name: &str, mut cmd_parser: ArgParser) -> Self {
+        if cmd_parser.helptext.is_some() || cmd_parser.generate_helptext {
             self.cmd_help = true;
         }
+        cmd_parser.aliases = name.split_whitespace().map(String::from).collect();
         self.commands.push(cmd_parser);
         let index = self.commands.len() - 1;
         for alias in name.split_whitespace() {
@@ -237,58 +878,380 @@ impl ArgParser {
         self
     }
 
-    /// Registers a callback function on a command parser. If the command is found the
-    /// function will be called and passed the command name and a reference to the
-    /// command's `ArgParser` instance.
-    pub fn callback(mut self, f: fn(&str, &ArgParser)) -> Self {
-        self.callback = Some(f);
+    /// This boolean switch toggles support for arguments which are not valid unicode strings.
+    /// If disabled, parsing will fail with an `InvalidUnicode` error if any argument is not
+    /// valid unicode. If enabled, the raw values of options and positional arguments are
+    /// available via `.value_os()`, `.values_os()`, and `.args_os()`, while the string-based
+    /// accessors return lossily-converted values. The value defaults to `false`.
+    pub fn allow_invalid_unicode(mut self, enable: bool) -> Self {
+        self.allow_invalid_unicode = enable;
         self
     }
 
-    /// Returns the value of the named option. Returns the default value registered
-    /// with the option if the option was not found. Any of the option's registered
-    /// aliases or shortcuts can be used for the `name` parameter.
-    /// (This function will panic if `name` is not a registered option name.)
-    pub fn value(&self, name: &str) -> String {
-        if let Some(index) = self.option_map.get(name) {
-            if let Some(value) = self.options[*index].values.last() {
-                return value.to_string();
-            }
-            return self.options[*index].default.clone();
-        }
-        panic!("'{}' is not a registered option name", name);
+    /// This boolean switch determines how the parser handles the automatic `--help` and
+    /// `--version` flags and the `help` and `completion` commands. If enabled, the parser
+    /// prints the requested text to `stdout` and exits. If disabled, the parser returns the
+    /// text as a `HelpRequested`, `VersionRequested`, or `CompletionRequested` error, which
+    /// can be printed by calling `.exit()` on the error.
+    /// The value defaults to `true`.
+    ///
+    /// ```
+    /// # use arguably::{ArgParser, ErrorKind};
+    /// let mut parser = ArgParser::new()
+    ///     .helptext("Usage: appname...")
+    ///     .exit_on_help(false);
+    ///
+    /// if let Err(err) = parser.parse_vec(vec!["--help"]) {
+    ///     assert_eq!(err.kind(), ErrorKind::HelpRequested);
+    ///     assert_eq!(err.message(), "Usage: appname...");
+    /// }
+    /// ```
+    pub fn exit_on_help(mut self, enable: bool) -> Self {
+        self.exit_on_help = enable;
+        self
     }
 
-    /// Returns the named option's list of values. Any of the option's registered
-    /// aliases or shortcuts can be used for the `name` parameter.
-    /// (This function will panic if `name` is not a registered option name.)
-    pub fn values(&self, name: &str) -> Vec<String> {
-        if let Some(index) = self.option_map.get(name) {
-            return self.options[*index].values.clone();
-        }
-        panic!("'{}' is not a registered option name", name);
+    /// This boolean switch toggles support for response files. If enabled, an argument of the
+    /// form `@path` is replaced by the arguments contained in the file at `path`. Arguments in
+    /// the file are separated by whitespace and can be quoted using shell-style single or
+    /// double quotes. Response files can include other response files. Arguments following a
+    /// `--` are not expanded. The value defaults to `false`.
+    pub fn response_files(mut self, enable: bool) -> Self {
+        self.response_file_prefix = if enable { Some('@') } else { None };
+        self
     }
 
-    /// Returns the number of times the named flag or option was found. Any registered
-    /// alias or shortcut can be used for the `name` parameter.
-    /// (This function will panic if `name` is not a registered flag or option name.)
-    pub fn count(&self, name: &str) -> usize {
-        if let Some(index) = self.flag_map.get(name) {
-            return self.flags[*index].count;
-        }
-        if let Some(index) = self.option_map.get(name) {
-            return self.options[*index].values.len();
-        }
-        panic!("'{}' is not a registered flag or option name", name);
+    /// Enables support for response files using a custom prefix character in place of `@`.
+    pub fn response_file_prefix(mut self, prefix: char) -> Self {
+        self.response_file_prefix = Some(prefix);
+        self
     }
 
-    /// Returns `true` if the named flag or option was found. Any registered alias or
-    /// shortcut can be used for the `name` parameter.
-    /// (This function will panic if `name` is not a registered flag or option name.)
+    /// Registers a callback function on a command parser. If the command is found the
+    /// function will be called and passed the command name and a reference to the
+    /// command's `ArgParser` instance. The function can be a closure which captures state.
+    /// Callbacks and handlers are only called once the whole command line has been parsed
+    /// and validated, starting with the innermost command.
+    pub fn callback<F>(mut self, mut f: F) -> Self where F: FnMut(&str, &ArgParser) + 'static {
+        self.callback = Some(Box::new(move |name, parser, _| {
+            f(name, parser);
+            Ok(())
+        }));
+        self
+    }
+
+    /// Registers a fallible handler function on a command parser. If the command is found the
+    /// function will be called and passed the command name and a reference to the command's
+    /// `ArgParser` instance. If the function returns an error, parsing will fail with that
+    /// error if it is an `arguably::Error` or with a `HandlerFailed` error wrapping it
+    /// otherwise.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .command("rm", ArgParser::new()
+    ///         .handler(|_, parser| {
+    ///             for path in &parser.args {
+    ///                 std::fs::remove_file(path)?;
+    ///             }
+    ///             Ok(())
+    ///         })
+    ///     );
+    /// ```
+    pub fn handler<F>(mut self, mut f: F) -> Self
+    where
+        F: FnMut(&str, &ArgParser) -> Result<(), Box<dyn error::Error>> + 'static
+    {
+        self.callback = Some(Box::new(move |name, parser, _| f(name, parser)));
+        self
+    }
+
+    /// Registers a fallible handler function on a command parser which also receives a
+    /// mutable reference to a user context of type `C`. The context is supplied by calling
+    /// `.parse_with()` or `.parse_vec_with()` on the root parser. If the command is found and
+    /// the parser was not given a context of type `C`, parsing will fail with a
+    /// `HandlerFailed` error.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .command("add", ArgParser::new()
+    ///         .handler_with(|_, parser, total: &mut i32| {
+    ///             for arg in &parser.args {
+    ///                 *total += arg.parse::<i32>()?;
+    ///             }
+    ///             Ok(())
+    ///         })
+    ///     );
+    ///
+    /// let mut total = 0;
+    /// parser.parse_vec_with(vec!["add", "1", "2"], &mut total).unwrap();
+    /// assert_eq!(total, 3);
+    /// ```
+    pub fn handler_with<C, F>(mut self, mut f: F) -> Self
+    where
+        C: 'static,
+        F: FnMut(&str, &ArgParser, &mut C) -> Result<(), Box<dyn error::Error>> + 'static
+    {
+        self.callback = Some(Box::new(move |name, parser, context| {
+            match context.downcast_mut::<C>() {
+                Some(context) => f(name, parser, context),
+                None => Err(format!(
+                    "the handler for '{}' expects a context of type {}",
+                    name, std::any::type_name::<C>()
+                ).into()),
+            }
+        }));
+        self
+    }
+
+    /// Returns the value of the named option or declared positional. Returns the default
+    /// value registered with the option if the option was not found, or an empty string for
+    /// a positional which was not supplied. Any of the option's registered aliases or
+    /// shortcuts can be used for the `name` parameter.
+    /// (This function will panic if `name` is not a registered option or positional name.)
+    pub fn value(&self, name: &str) -> String {
+        self.value_os(name).to_string_lossy().into_owned()
+    }
+
+    /// Returns the raw value of the named option or declared positional. Returns the default
+    /// value registered with the option if the option was not found, or an empty string for
+    /// a positional which was not supplied. Any of the option's registered aliases or
+    /// shortcuts can be used for the `name` parameter.
+    /// (This function will panic if `name` is not a registered option or positional name.)
+    pub fn value_os(&self, name: &str) -> OsString {
+        if let Some(index) = self.option_map.get(name) {
+            if let Some(value) = self.options[*index].values.last() {
+                return value.clone();
+            }
+            return OsString::from(&self.options[*index].default);
+        }
+        if let Some(index) = self.positional_index(name) {
+            return self.positionals[index].values.last().cloned().unwrap_or_default();
+        }
+        panic!("'{}' is not a registered option or positional name", name);
+    }
+
+    /// Returns the named option's or declared positional's list of values. Any of the
+    /// option's registered aliases or shortcuts can be used for the `name` parameter.
+    /// (This function will panic if `name` is not a registered option or positional name.)
+    pub fn values(&self, name: &str) -> Vec<String> {
+        self.values_os(name).iter().map(|value| value.to_string_lossy().into_owned()).collect()
+    }
+
+    /// Returns the named option's or declared positional's list of raw values. Any of the
+    /// option's registered aliases or shortcuts can be used for the `name` parameter.
+    /// (This function will panic if `name` is not a registered option or positional name.)
+    pub fn values_os(&self, name: &str) -> Vec<OsString> {
+        if let Some(index) = self.option_map.get(name) {
+            return self.options[*index].values.clone();
+        }
+        if let Some(index) = self.positional_index(name) {
+            return self.positionals[index].values.clone();
+        }
+        panic!("'{}' is not a registered option or positional name", name);
+    }
+
+    /// Returns the named option's values grouped by occurrence, i.e. one list of values for
+    /// each time the option was found. Any of the option's registered aliases or shortcuts can
+    /// be used for the `name` parameter.
+    /// (This function will panic if `name` is not a registered option name.)
+    pub fn occurrences(&self, name: &str) -> Vec<Vec<String>> {
+        let opt = match self.option_map.get(name) {
+            Some(index) => &self.options[*index],
+            None => panic!("'{}' is not a registered option name", name),
+        };
+        let mut values = opt.values.iter().map(|value| value.to_string_lossy().into_owned());
+        opt.occurrences.iter().map(|count| values.by_ref().take(*count).collect()).collect()
+    }
+
+    /// Returns the list of raw positional arguments.
+    pub fn args_os(&self) -> Vec<OsString> {
+        self.args_os.clone()
+    }
+
+    /// Returns the value of the named option converted to type `T`. Returns an
+    /// `InvalidValue` error if the value cannot be parsed.
+    /// (This function will panic if `name` is not a registered option name.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .option("port p", "8080");
+    ///
+    /// let port: u16 = parser.value_as("port").unwrap();
+    /// ```
+    pub fn value_as<T: FromStr>(&self, name: &str) -> Result<T, Error> {
+        let value = self.value(name);
+        value.parse::<T>().map_err(|_| Error::new(ErrorKind::InvalidValue,
+            format!("'{}' is not a valid value for {}", value, self.value_display_name(name))
+        ).with_token(&value))
+    }
+
+    /// Returns the named option's list of values converted to type `T`. Returns an
+    /// `InvalidValue` error if any of the values cannot be parsed.
+    /// (This function will panic if `name` is not a registered option name.)
+    pub fn values_as<T: FromStr>(&self, name: &str) -> Result<Vec<T>, Error> {
+        self.values(name).iter().map(|value| {
+            value.parse::<T>().map_err(|_| Error::new(ErrorKind::InvalidValue,
+                format!("'{}' is not a valid value for {}", value, self.value_display_name(name))
+            ).with_token(value))
+        }).collect()
+    }
+
+    // Returns the name of a registered option or positional in command line form, e.g.
+    // `--port` or `<input>`.
+    fn value_display_name(&self, name: &str) -> String {
+        match self.option_map.get(name) {
+            Some(index) => self.options[*index].display_name(),
+            None => format!("<{}>", name),
+        }
+    }
+
+    /// Returns the positional argument at `index` converted to type `T`. Returns a
+    /// `MissingValue` error if there is no argument at `index` or an `InvalidValue` error if
+    /// the argument cannot be parsed.
+    pub fn arg_as<T: FromStr>(&self, index: usize) -> Result<T, Error> {
+        if let Some(arg) = self.args.get(index) {
+            arg.parse::<T>().map_err(|_| Error::new(ErrorKind::InvalidValue,
+                format!("'{}' is not a valid value for argument {}", arg, index + 1)
+            ).with_token(arg))
+        } else {
+            Err(Error::new(ErrorKind::MissingValue, format!("missing argument {}", index + 1)))
+        }
+    }
+
+    /// Returns the number of times the named flag or option was found, or the number of
+    /// arguments assigned to the named positional. Any registered alias or shortcut can be
+    /// used for the `name` parameter.
+    /// (This function will panic if `name` is not a registered flag, option, or positional
+    /// name.)
+    pub fn count(&self, name: &str) -> usize {
+        if let Some(index) = self.flag_map.get(name) {
+            return self.flags[*index].count;
+        }
+        if let Some(index) = self.option_map.get(name) {
+            return self.options[*index].occurrences.len();
+        }
+        if let Some(index) = self.positional_index(name) {
+            return self.positionals[index].values.len();
+        }
+        panic!("'{}' is not a registered flag, option, or positional name", name);
+    }
+
+    /// Returns `true` if the named flag, option, or positional was found. Any registered
+    /// alias or shortcut can be used for the `name` parameter.
+    /// (This function will panic if `name` is not a registered flag, option, or positional
+    /// name.)
     pub fn found(&self, name: &str) -> bool {
         self.count(name) > 0
     }
 
+    /// Returns the effective value of the named flag: `false` if the form seen last on the
+    /// command line was a `--no-<name>` negation, otherwise `true` if the flag was found or
+    /// enabled by an environment variable or config file. Any registered alias or shortcut can
+    /// be used for the `name` parameter.
+    /// (This function will panic if `name` is not a registered flag name.)
+    pub fn enabled(&self, name: &str) -> bool {
+        let flag = self.registered_flag(name);
+        flag.last_seen.unwrap_or(flag.count > 0)
+    }
+
+    /// Returns `true` if the form of the named flag seen last on the command line was a
+    /// `--no-<name>` negation. Any registered alias or shortcut can be used for the `name`
+    /// parameter.
+    /// (This function will panic if `name` is not a registered flag name.)
+    pub fn negated(&self, name: &str) -> bool {
+        self.registered_flag(name).last_seen == Some(false)
+    }
+
+    /// Returns the canonical names of the commands found, from the outermost to the
+    /// innermost, e.g. `["remote", "add"]` for `appname remote add`. A command's canonical
+    /// name is the first alias it was registered with. The list is empty if no command was
+    /// found.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .command("remote", ArgParser::new()
+    ///         .command("add a", ArgParser::new())
+    ///     );
+    ///
+    /// parser.parse_vec(vec!["remote", "a"]).unwrap();
+    /// assert_eq!(parser.command_path(), ["remote", "add"]);
+    /// ```
+    pub fn command_path(&self) -> Vec<String> {
+        let mut path = Vec::new();
+        let mut parser = self;
+        while let Some(cmd_parser) = &parser.cmd_parser {
+            path.push(cmd_parser.canonical_name());
+            parser = cmd_parser;
+        }
+        path
+    }
+
+    /// Returns the parser of the innermost command found, or the parser itself if no command
+    /// was found.
+    pub fn leaf(&self) -> &ArgParser {
+        let mut parser = self;
+        while let Some(cmd_parser) = &parser.cmd_parser {
+            parser = cmd_parser;
+        }
+        parser
+    }
+
+    /// Looks up a command's parser by its path of command names, e.g. `&["remote", "add"]`.
+    /// Any registered alias can be used for each name. Before parsing, the path is looked up
+    /// in the registered commands; after parsing, only the commands found are available.
+    /// Returns `None` if there is no command at the path. An empty path returns the parser
+    /// itself.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .command("remote", ArgParser::new()
+    ///         .command("add", ArgParser::new().flag("force"))
+    ///         .command("remove", ArgParser::new())
+    ///     );
+    ///
+    /// parser.parse_vec(vec!["remote", "add", "--force"]).unwrap();
+    /// assert!(parser.find_command(&["remote", "add"]).unwrap().found("force"));
+    /// assert!(parser.find_command(&["remote", "remove"]).is_none());
+    /// ```
+    pub fn find_command(&self, path: &[&str]) -> Option<&ArgParser> {
+        let mut parser = self;
+        for name in path {
+            parser = match &parser.cmd_parser {
+                Some(cmd_parser) if cmd_parser.aliases.iter().any(|a| a == name) => cmd_parser,
+                Some(_) => return None,
+                None => &parser.commands[*parser.command_map.get(*name)?],
+            };
+        }
+        Some(parser)
+    }
+
+    // Returns the command's canonical name, i.e. the first alias it was registered with.
+    fn canonical_name(&self) -> String {
+        self.aliases.first().cloned().unwrap_or_default()
+    }
+
+    fn registered_flag(&self, name: &str) -> &Flag {
+        match self.flag_map.get(name) {
+            Some(index) => &self.flags[*index],
+            None => panic!("'{}' is not a registered flag name", name),
+        }
+    }
+
+    // Returns the index of the negatable flag negated by a long-form name like `no-foo`.
+    fn negated_flag_index(&self, name: &str) -> Option<usize> {
+        let index = *self.flag_map.get(name.strip_prefix("no-")?)?;
+        let flag = &self.flags[index];
+        if flag.negatable && name.chars().count() > 4 {
+            return Some(index);
+        }
+        None
+    }
+
     /// Parse the program's command line arguments.
     ///
     /// ```
@@ -298,117 +1261,349 @@ impl ArgParser {
     /// }
     /// ```
     pub fn parse(&mut self) -> Result<(), Error> {
-        let mut strings = Vec::<String>::new();
-        for os_string in std::env::args_os().skip(1) {
-            if let Ok(string) = os_string.into_string() {
-                strings.push(string);
-            } else {
-                return Err(Error::InvalidUnicode);
-            }
-        }
-        let mut stream = ArgStream::new(strings);
-        self.parse_argstream(&mut stream)?;
-        Ok(())
+        self.parse_os_vec(std::env::args_os().skip(1).collect())
+    }
+
+    /// Parse the program's command line arguments, passing a mutable reference to `context`
+    /// to any command handlers registered via `.handler_with()`.
+    pub fn parse_with<C: 'static>(&mut self, context: &mut C) -> Result<(), Error> {
+        self.parse_os_vec_with_context(std::env::args_os().skip(1).collect(), context)
     }
 
     /// Parse a vector of arguments.
     pub fn parse_vec(&mut self, args: Vec<&str>) -> Result<(), Error> {
-        let strings = args.iter().map(|s| s.to_string()).collect();
-        let mut stream = ArgStream::new(strings);
-        self.parse_argstream(&mut stream)?;
+        self.parse_os_vec(args.iter().map(OsString::from).collect())
+    }
+
+    /// Parse a vector of arguments, passing a mutable reference to `context` to any command
+    /// handlers registered via `.handler_with()`.
+    pub fn parse_vec_with<C>(&mut self, args: Vec<&str>, context: &mut C) -> Result<(), Error>
+    where
+        C: 'static,
+    {
+        self.parse_os_vec_with_context(args.iter().map(OsString::from).collect(), context)
+    }
+
+    /// Parse a vector of raw arguments. Arguments which are not valid unicode strings are
+    /// rejected unless support has been enabled via `.allow_invalid_unicode()`.
+    pub fn parse_os_vec(&mut self, args: Vec<OsString>) -> Result<(), Error> {
+        self.parse_os_vec_with_context(args, &mut ())
+    }
+
+    fn parse_os_vec_with_context(
+        &mut self,
+        args: Vec<OsString>,
+        context: &mut dyn Any,
+    ) -> Result<(), Error> {
+        if !self.allow_invalid_unicode {
+            if let Some(index) = args.iter().position(|arg| arg.to_str().is_none()) {
+                let msg = format!("argument {} is not a valid unicode string", index + 1);
+                let err = Error::new(ErrorKind::InvalidUnicode, msg)
+                    .with_token(&args[index].to_string_lossy())
+                    .at(index + 1);
+                return Err(err);
+            }
+        }
+        let mut stream = match self.response_file_prefix {
+            Some(prefix) => ArgStream::with_response_files(args, prefix)?,
+            None => ArgStream::new(args),
+        };
+        match self.parse_argstream(&mut stream) {
+            Err(err) if self.exit_on_help && err.is_exit_request() => err.exit(),
+            Err(err) => Err(err),
+            Ok(()) => self.run_handlers(context),
+        }
+    }
+
+    // Calls the handlers of the commands found, innermost command first. Handlers are only
+    // called once the whole command line has been parsed and validated.
+    fn run_handlers(&mut self, context: &mut dyn Any) -> Result<(), Error> {
+        let name = self.cmd_name.clone();
+        if let (Some(name), Some(mut cmd_parser)) = (name, self.cmd_parser.take()) {
+            let canonical = cmd_parser.canonical_name();
+            let mut result = cmd_parser.run_handlers(context)
+                .map_err(|err| err.in_command(&canonical));
+            if let (Ok(()), Some(mut callback)) = (&result, cmd_parser.callback.take()) {
+                result = callback(&name, &cmd_parser, context)
+                    .map_err(|err| Error::from_handler(err).in_command(&canonical));
+                cmd_parser.callback = Some(callback);
+            }
+            self.cmd_parser = Some(cmd_parser);
+            result?;
+        }
         Ok(())
     }
 
     fn parse_argstream(&mut self, argstream: &mut ArgStream) -> Result<(), Error> {
-        let mut is_first_arg = true;
-
         while argstream.has_next() {
-            let arg = argstream.next();
+            // Commands are recognised in the position of the first positional argument, so
+            // flags and options for the parser itself can precede the command name.
+            let is_first_positional = self.args_os.is_empty();
+            let raw_arg = argstream.next_os();
+            let arg = raw_arg.to_string_lossy().into_owned();
+            let position = argstream.position();
+            let locate = |err: Error| err.with_token(&arg).at(position);
+            let name = match is_first_positional && !arg.starts_with('-') {
+                true => self.expand_command_name(&arg).map_err(locate)?,
+                false => arg.clone(),
+            };
 
             if arg == "--" {
                 while argstream.has_next() {
-                    self.args.push(argstream.next());
+                    self.push_arg(argstream.next_os());
                 }
             }
 
             else if arg.starts_with("--") {
-                if arg.contains("=") {
-                    self.handle_equals_opt(&arg)?;
+                let expanded = self.expand_long_arg(&arg).map_err(locate)?;
+                if expanded.contains('=') {
+                    self.handle_equals_opt(&expanded, &raw_arg, argstream).map_err(locate)?;
                 } else {
-                    self.handle_long_opt(&arg, argstream)?;
+                    self.handle_long_opt(&expanded, argstream).map_err(locate)?;
                 }
             }
 
-            else if arg.starts_with("-") {
+            else if arg.starts_with('-') {
                 if arg == "-" || arg.chars().nth(1).unwrap().is_numeric() {
-                    self.args.push(arg);
-                } else if arg.contains("=") {
-                    self.handle_equals_opt(&arg)?;
+                    self.push_arg(raw_arg);
+                } else if arg.contains('=') {
+                    self.handle_equals_opt(&arg, &raw_arg, argstream).map_err(locate)?;
                 } else {
-                    self.handle_short_opt(&arg, argstream)?;
+                    self.handle_short_opt(&arg, argstream).map_err(locate)?;
                 }
             }
 
-            else if is_first_arg && self.command_map.contains_key(&arg) {
-                let index = self.command_map.get(&arg).unwrap();
+            else if is_first_positional && self.command_map.contains_key(&name) {
+                let index = self.command_map.get(&name).unwrap();
                 let mut cmd_parser = self.commands.remove(*index);
                 self.command_map.clear();
                 self.commands.clear();
-                cmd_parser.parse_argstream(argstream)?;
-                if let Some(callback) = cmd_parser.callback {
-                    callback(&arg, &cmd_parser);
-                }
-                self.cmd_name = Some(arg);
+                self.prepare_cmd_parser(&mut cmd_parser, &name)?;
+                let canonical = cmd_parser.aliases.first().cloned().unwrap_or_else(|| name.clone());
+                cmd_parser.parse_argstream(argstream).map_err(|err| err.in_command(&canonical))?;
+                self.collect_globals(&cmd_parser);
+                self.cmd_name = Some(name);
                 self.cmd_parser = Some(Box::new(cmd_parser));
             }
 
-            else if is_first_arg && self.cmd_help && arg == "help" {
+            else if is_first_positional && self.cmd_help && name == "help" {
                 if argstream.has_next() {
                     let name = argstream.next();
+                    let name = self.expand_command_name(&name)
+                        .map_err(|err| err.with_token(&name).at(argstream.position()))?;
                     if let Some(index) = self.command_map.get(&name) {
-                        let cmd_parser = &mut self.commands[*index];
-                        let helptext = cmd_parser.helptext.as_deref().unwrap_or("").trim();
-                        println!("{}", helptext);
-                        std::process::exit(0);
+                        let mut cmd_parser = self.commands.remove(*index);
+                        self.prepare_cmd_parser(&mut cmd_parser, &name)?;
+                        let helptext = cmd_parser.render_helptext();
+                        return Err(Error::new(ErrorKind::HelpRequested, helptext.trim()));
                     } else {
-                        return Err(Error::InvalidName(
-                            format!("'{}' is not a recognised command name", &name)
-                        ));
+                        let err = self.invalid_command_name(&name);
+                        return Err(err.with_token(&name).at(argstream.position()));
                     }
                 } else {
-                    return Err(Error::MissingHelpArg);
+                    let msg = "missing argument for the help command";
+                    let err = Error::new(ErrorKind::MissingHelpArg, msg);
+                    return Err(locate(err));
+                }
+            }
+
+            else if is_first_positional && self.cmd_completion && name == "completion" {
+                if argstream.has_next() {
+                    let name = argstream.next();
+                    let shell = name.parse::<Shell>()
+                        .map_err(|err| err.with_token(&name).at(argstream.position()))?;
+                    let script = self.completion_script(shell);
+                    return Err(Error::new(ErrorKind::CompletionRequested, script.trim()));
+                } else {
+                    let msg = "missing argument for the completion command";
+                    let err = Error::new(ErrorKind::MissingValue, msg);
+                    return Err(locate(err));
                 }
             }
 
+            // If the parser has commands but accepts no positional arguments, the first positional
+            // argument must be a mistyped command name.
+            else if is_first_positional && !self.commands.is_empty() && self.max_args == Some(0) {
+                return Err(locate(self.invalid_command_name(&arg)));
+            }
+
             else {
-                self.args.push(arg);
+                self.push_arg(raw_arg);
             }
+        }
 
-            is_first_arg = false;
+        self.apply_fallbacks()?;
+        self.share_globals();
+        self.validate()?;
+        if self.cmd_name.is_none() {
+            self.assign_positionals()?;
         }
+        Ok(())
+    }
+
+    // Looks up the named environment variable using the registered source.
+    fn env_var(&self, var: &str) -> Option<String> {
+        match &self.env_source {
+            Some(source) => source(var),
+            None => std::env::var(var).ok(),
+        }
+    }
 
+    // Fills in values for any flags or options which were not found on the command line,
+    // first from environment variables, then from config files. Inherited global flags and
+    // options are left to the parser which registered them.
+    fn apply_fallbacks(&mut self) -> Result<(), Error> {
+        let config = self.load_config()?;
+        let overridden = self.overridden_fallbacks();
+        let is_overridden = |names: &[String]| {
+            matches!(names.first(), Some(name) if overridden.contains(name))
+        };
+
+        for index in 0..self.options.len() {
+            let opt = &self.options[index];
+            if !opt.values.is_empty() || opt.inherited || is_overridden(&opt.names) {
+                continue;
+            }
+            if let Some(var) = &self.options[index].env {
+                if let Some(value) = self.env_var(var) {
+                    self.options[index].push(OsString::from(value))?;
+                    continue;
+                }
+            }
+            if let Some(values) = config.get(&self.config_section, &self.options[index].names) {
+                for value in values {
+                    self.options[index].push(OsString::from(value))?;
+                }
+            }
+        }
+
+        for index in 0..self.flags.len() {
+            let flag = &self.flags[index];
+            if flag.count > 0 || flag.last_seen.is_some() || flag.inherited {
+                continue;
+            }
+            if is_overridden(&flag.names) {
+                continue;
+            }
+            let mut value = None;
+            if let Some(var) = &self.flags[index].env {
+                if let Some(env_value) = self.env_var(var) {
+                    value = Some((env_value, format!("environment variable {}", var)));
+                }
+            }
+            if value.is_none() {
+                if let Some(values) = config.get(&self.config_section, &self.flags[index].names) {
+                    if let Some(config_value) = values.last() {
+                        let name = display_name(&self.flags[index].names);
+                        value = Some((config_value.clone(), format!("config entry for {}", name)));
+                    }
+                }
+            }
+            if let Some((value, source)) = value {
+                let enabled = parse_bool(&value).ok_or_else(|| {
+                    let msg = format!("'{}' is not a valid boolean value for {}", value, source);
+                    Error::new(ErrorKind::InvalidValue, msg)
+                })?;
+                if enabled {
+                    self.flags[index].count = 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Checks that all required options were found, that the registered constraints are
+    // satisfied, and that the number of positional arguments is within the registered limits.
+    // Arguments following a command belong to the command, so their number is not checked if
+    // a command was found.
+    fn validate(&self) -> Result<(), Error> {
+        for opt in &self.options {
+            if opt.required && opt.values.is_empty() && !opt.inherited {
+                return Err(Error::new(ErrorKind::MissingRequired,
+                    format!("missing required option {}", opt.display_name())
+                ));
+            }
+        }
+
+        self.check_constraints()?;
+
+        if self.cmd_name.is_some() {
+            return Ok(());
+        }
+
+        if self.args.len() < self.min_args {
+            return Err(Error::new(ErrorKind::TooFewArgs, format!(
+                "expected at least {} {}, found {}",
+                self.min_args, plural(self.min_args, "argument", "arguments"), self.args.len()
+            )));
+        }
+
+        if let Some(max_args) = self.max_args {
+            if self.args.len() > max_args {
+                return Err(Error::new(ErrorKind::TooManyArgs, format!(
+                    "expected at most {} {}, found {}",
+                    max_args, plural(max_args, "argument", "arguments"), self.args.len()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_arg(&mut self, arg: OsString) {
+        self.args.push(arg.to_string_lossy().into_owned());
+        self.args_os.push(arg);
+    }
+
+    // Passes inherited settings down to a command's parser before it takes over the stream.
+    fn prepare_cmd_parser(&mut self, cmd_parser: &mut ArgParser, name: &str) -> Result<(), Error> {
+        cmd_parser.name = Some(format!("{} {}", self.app_name(), name));
+        if self.generate_helptext {
+            cmd_parser.generate_helptext = true;
+        }
+        if self.allow_abbreviations {
+            cmd_parser.allow_abbreviations = true;
+        }
+        if cmd_parser.env_source.is_none() {
+            cmd_parser.env_source = self.env_source.clone();
+        }
+        let canonical = cmd_parser.aliases.first().map(String::as_str).unwrap_or(name);
+        cmd_parser.config_section = if self.config_section.is_empty() {
+            canonical.to_string()
+        } else {
+            format!("{}.{}", self.config_section, canonical)
+        };
+        cmd_parser.parent_config = Some(self.load_config()?);
+        self.inherit_globals(cmd_parser);
+        for name in self.config_option.iter().chain(&self.inherited_config_option) {
+            if cmd_parser.inherits(&self.options[self.option_map[name]].names) {
+                cmd_parser.inherited_config_option = Some(name.clone());
+            }
+        }
         Ok(())
     }
 
     fn handle_long_opt(&mut self, arg: &str, argstream: &mut ArgStream) -> Result<(), Error> {
         if let Some(index) = self.flag_map.get(&arg[2..]) {
             self.flags[*index].count += 1;
-        } else if let Some(index) = self.option_map.get(&arg[2..]) {
-            if argstream.has_next() {
-                self.options[*index].values.push(argstream.next());
+            self.flags[*index].last_seen = Some(true);
+        } else if let Some(index) = self.negated_flag_index(&arg[2..]) {
+            self.flags[index].last_seen = Some(false);
+        } else if let Some(&index) = self.option_map.get(&arg[2..]) {
+            if let Some(value) = self.options[index].implicit_value.clone() {
+                self.options[index].push(OsString::from(value))?;
             } else {
-                return Err(Error::MissingValue(format!("missing value for {}", arg)));
-            }
-        } else if arg == "--help" && self.helptext.is_some() {
-            println!("{}", self.helptext.as_ref().unwrap().trim());
-            std::process::exit(0);
-        } else if arg == "--version" && self.version.is_some() {
-            println!("{}", self.version.as_ref().unwrap().trim());
-            std::process::exit(0);
+                self.read_values(index, None, arg, argstream)?;
+            }
+        } else if arg == "--help" && self.has_helptext() {
+            return Err(Error::new(ErrorKind::HelpRequested, self.render_helptext().trim()));
+        } else if let (true, Some(version)) = (arg == "--version", &self.version) {
+            return Err(Error::new(ErrorKind::VersionRequested, version.trim()));
         } else {
-            return Err(Error::InvalidName(
-                format!("{} is not a recognised flag or option name", arg)
-            ));
+            let suggestions = self.suggest_long_names(&arg[2..], true);
+            let msg = format!("{} is not a recognised flag or option name", arg);
+            return Err(invalid_name(msg, suggestions));
         }
         Ok(())
     }
@@ -417,88 +1612,425 @@ impl ArgParser {
         for c in arg.chars().skip(1) {
             if let Some(index) = self.flag_map.get(&c.to_string()) {
                 self.flags[*index].count += 1;
-            } else if let Some(index) = self.option_map.get(&c.to_string()) {
-                if argstream.has_next() {
-                    self.options[*index].values.push(argstream.next());
+                self.flags[*index].last_seen = Some(true);
+            } else if let Some(&index) = self.option_map.get(&c.to_string()) {
+                if let Some(value) = self.options[index].implicit_value.clone() {
+                    self.options[index].push(OsString::from(value))?;
+                } else if arg.chars().count() > 2 {
+                    self.read_values(index, None, &format!("'{}' in {}", c, arg), argstream)?;
                 } else {
-                    let msg = if arg.chars().count() > 2 {
-                        format!("missing value for '{}' in {}", c, arg)
-                    } else {
-                        format!("missing value for {}", arg)
-                    };
-                    return Err(Error::MissingValue(msg));
+                    self.read_values(index, None, arg, argstream)?;
                 }
-            } else if c == 'h' && self.helptext.is_some() {
-                println!("{}", self.helptext.as_ref().unwrap().trim());
-                std::process::exit(0);
-            } else if c == 'v' && self.version.is_some() {
-                println!("{}", self.version.as_ref().unwrap().trim());
-                std::process::exit(0);
+            } else if c == 'h' && self.has_helptext() {
+                return Err(Error::new(ErrorKind::HelpRequested, self.render_helptext().trim()));
+            } else if let (true, Some(version)) = (c == 'v', &self.version) {
+                return Err(Error::new(ErrorKind::VersionRequested, version.trim()));
             } else {
-                let msg = if arg.chars().count() > 2 {
-                    format!("'{}' in {} is not a recognised flag or option name", c, arg)
-                } else {
-                    format!("{} is not a recognised flag or option name", arg)
-                };
-                return Err(Error::InvalidName(msg));
+                // A multi-character argument with an unrecognised character may be a long name
+                // missing one of its dashes, e.g. -verbose.
+                if arg.chars().count() > 2 {
+                    let msg = format!("'{}' in {} is not a recognised flag or option name", c, arg);
+                    return Err(invalid_name(msg, self.suggest_long_names(&arg[1..], true)));
+                }
+                let msg = format!("{} is not a recognised flag or option name", arg);
+                return Err(invalid_name(msg, Vec::new()));
             }
         }
         Ok(())
     }
 
-    fn handle_equals_opt(&mut self, arg: &str) -> Result<(), Error> {
+    fn handle_equals_opt(
+        &mut self,
+        arg: &str,
+        raw_arg: &OsStr,
+        argstream: &mut ArgStream,
+    ) -> Result<(), Error> {
         let splits: Vec<&str> = arg.splitn(2, '=').collect();
         let name = splits[0];
         let value = splits[1];
 
-        if let Some(index) = self.option_map.get(name.trim_start_matches('-')) {
-            if value == "" {
-                return Err(Error::MissingValue(format!("missing value for {}", name)));
+        if let Some(&index) = self.option_map.get(name.trim_start_matches('-')) {
+            if value.is_empty() {
+                let msg = format!("missing value for {}", name);
+                return Err(Error::new(ErrorKind::MissingValue, msg));
             } else {
-                self.options[*index].values.push(value.to_string());
-                return Ok(());
+                let raw_value = split_equals_os(raw_arg).unwrap_or_else(|| OsString::from(value));
+                return self.read_values(index, Some(raw_value), name, argstream);
             }
         }
-        return Err(Error::InvalidName(format!("{} is not a recognised option name", name)));
+        let suggestions = match name.strip_prefix("--") {
+            Some(long) => self.suggest_long_names(long, false),
+            None => Vec::new(),
+        };
+        Err(invalid_name(format!("{} is not a recognised option name", name), suggestions))
+    }
+
+    // Reads the values for an occurrence of an option according to its arity. The `first`
+    // value, if any, is the value attached to the option name using `=`. Following arguments
+    // are read as values until the option's minimum is reached, then up to its maximum or the
+    // next flag or option. The `name` parameter describes the option in error messages.
+    fn read_values(
+        &mut self,
+        index: usize,
+        first: Option<OsString>,
+        name: &str,
+        argstream: &mut ArgStream,
+    ) -> Result<(), Error> {
+        let opt = &self.options[index];
+        let (min, max) = opt.arity.bounds();
+        let mut values = Vec::new();
+        let mut found = 0;
+
+        if let Some(value) = first {
+            values.extend(opt.check_all(value)?);
+            found += 1;
+        }
+
+        while argstream.has_next() && !matches!(max, Some(max) if found >= max) {
+            if found >= min && matches!(argstream.peek(), Some(arg) if is_option_like(&arg)) {
+                break;
+            }
+            let value = argstream.next_os();
+            values.extend(opt.check_all(value).map_err(|err| err.at(argstream.position()))?);
+            found += 1;
+        }
+
+        if found < min {
+            let msg = match opt.arity {
+                Arity::Exactly(1) => format!("missing value for {}", name),
+                Arity::Exactly(n) => format!(
+                    "missing value for {} (expected {} {}, found {})",
+                    name, n, plural(n, "value", "values"), found
+                ),
+                _ => format!(
+                    "missing value for {} (expected at least {} {}, found {})",
+                    name, min, plural(min, "value", "values"), found
+                ),
+            };
+            return Err(Error::new(ErrorKind::MissingValue, msg));
+        }
+
+        self.options[index].push_occurrence(values);
+        Ok(())
+    }
+
+    // Returns the registered long names closest to `name` in command line form. Flag names
+    // (including the automatic --help and --version flags) are only included if `flags` is true.
+    fn suggest_long_names(&self, name: &str, flags: bool) -> Vec<String> {
+        let mut candidates: Vec<&str> = self.option_map.keys().map(String::as_str).collect();
+        let negations: Vec<String> = self.flags.iter().flat_map(Flag::negations).collect();
+        if flags {
+            candidates.extend(self.flag_map.keys().map(String::as_str));
+            candidates.extend(negations.iter().map(String::as_str));
+            if self.has_helptext() {
+                candidates.push("help");
+            }
+            if self.version.is_some() {
+                candidates.push("version");
+            }
+        }
+        let candidates = candidates.into_iter()
+            .filter(|candidate| candidate.chars().count() > 1)
+            .map(String::from);
+        suggest::suggestions(name, candidates).into_iter().map(|s| format!("--{}", s)).collect()
+    }
+
+    // Returns an InvalidName error for an unrecognised command name.
+    fn invalid_command_name(&self, name: &str) -> Error {
+        let mut candidates: Vec<String> = self.command_map.keys().cloned().collect();
+        if self.cmd_help {
+            candidates.push("help".to_string());
+        }
+        let suggestions = suggest::suggestions(name, candidates);
+        invalid_name(format!("'{}' is not a recognised command name", name), suggestions)
     }
 }
 
 
+impl Default for ArgParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+// Parses a boolean value from an environment variable or config file.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" | "" => Some(false),
+        _ => None,
+    }
+}
+
+
+// Returns an InvalidName error, appending any suggestions to the message.
+fn invalid_name(message: String, suggestions: Vec<String>) -> Error {
+    let message = format!("{}{}", message, suggest::format(&suggestions));
+    let mut err = Error::new(ErrorKind::InvalidName, message);
+    err.inner.suggestions = suggestions;
+    err
+}
+
+
+// Splits a value on a delimiter character. A backslash escapes a following delimiter or
+// backslash; any other backslash is kept as is.
+fn split_delimited(value: &str, delimiter: char) -> Vec<OsString> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' && matches!(chars.peek(), Some(&next) if next == delimiter || next == '\\') {
+            part.push(chars.next().unwrap());
+        } else if c == delimiter {
+            parts.push(OsString::from(std::mem::take(&mut part)));
+        } else {
+            part.push(c);
+        }
+    }
+
+    parts.push(OsString::from(part));
+    parts
+}
+
+
+// Returns true if an argument would be parsed as a flag or option, or is a `--` separator.
+// Used to find the end of a variable-length list of option values.
+fn is_option_like(arg: &str) -> bool {
+    arg.starts_with('-') && arg != "-" && !matches!(arg.chars().nth(1), Some(c) if c.is_numeric())
+}
+
+
+// Returns `singular` if `count` is 1, otherwise `plural`.
+fn plural<'a>(count: usize, singular: &'a str, plural: &'a str) -> &'a str {
+    if count == 1 { singular } else { plural }
+}
+
+
+// Returns the raw value following the first '=' in an argument of the form `--name=value`.
+// On platforms other than unix we can't split an OsStr directly so we return None and the
+// caller falls back to the lossily-converted value.
+#[cfg(unix)]
+fn split_equals_os(arg: &OsStr) -> Option<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+    let bytes = arg.as_bytes();
+    let index = bytes.iter().position(|b| *b == b'=')?;
+    Some(OsStr::from_bytes(&bytes[index + 1..]).to_os_string())
+}
+
+
+#[cfg(not(unix))]
+fn split_equals_os(_arg: &OsStr) -> Option<OsString> {
+    None
+}
+
+
 // This type functions as a wrapper to make the input argument vector available as a stream.
+// Each argument is stored with its position in the original argument list, starting from 1.
 struct ArgStream {
-    args: Vec<String>,
+    args: Vec<(usize, OsString)>,
     index: usize,
 }
 
 
 impl ArgStream {
-    fn new(args: Vec<String>) -> ArgStream {
+    fn new(args: Vec<OsString>) -> ArgStream {
         ArgStream {
-            args: args,
+            args: args.into_iter().enumerate().map(|(index, arg)| (index + 1, arg)).collect(),
             index: 0,
         }
     }
 
+    // Creates a stream with any response file arguments expanded. Arguments read from a
+    // response file take the position of the response file argument.
+    fn with_response_files(args: Vec<OsString>, prefix: char) -> Result<ArgStream, Error> {
+        Ok(ArgStream {
+            args: response::expand(args, prefix)?,
+            index: 0,
+        })
+    }
+
+    // Returns the original position of the most recently returned argument.
+    fn position(&self) -> usize {
+        self.args[self.index - 1].0
+    }
+
     fn has_next(&self) -> bool {
         self.index < self.args.len()
     }
 
     fn next(&mut self) -> String {
+        self.next_os().to_string_lossy().into_owned()
+    }
+
+    fn peek(&self) -> Option<String> {
+        self.args.get(self.index).map(|(_, arg)| arg.to_string_lossy().into_owned())
+    }
+
+    fn next_os(&mut self) -> OsString {
         self.index += 1;
-        self.args[self.index - 1].clone()
+        self.args[self.index - 1].1.clone()
     }
 }
 
 
 // We create a single Opt instance for each registered option, i.e. each call to `.option()`.
+#[derive(Clone)]
 struct Opt {
-    values: Vec<String>,
+    names: Vec<String>,
+    values: Vec<OsString>,
+    // The number of values added by each occurrence of the option.
+    occurrences: Vec<usize>,
+    arity: Arity,
+    delimiter: Option<char>,
+    global: bool,
+    // True for a copy of a global option inherited from a parent parser.
+    inherited: bool,
     default: String,
+    description: Option<String>,
+    value_name: Option<String>,
+    required: bool,
+    env: Option<String>,
+    choices: Vec<String>,
+    ignore_case: bool,
+    validator: Option<Rc<Validator>>,
+    implicit_value: Option<String>,
+}
+
+
+impl Opt {
+    // Checks a new value and adds it to the option's list of values as a single occurrence.
+    fn push(&mut self, value: OsString) -> Result<(), Error> {
+        let values = self.check_all(value)?;
+        self.push_occurrence(values);
+        Ok(())
+    }
+
+    // Splits a new value on the option's delimiter, if any, and checks each part.
+    fn check_all(&self, value: OsString) -> Result<Vec<OsString>, Error> {
+        let parts = match (self.delimiter, value.to_str()) {
+            (Some(delimiter), Some(string)) => split_delimited(string, delimiter),
+            _ => vec![value],
+        };
+        parts.into_iter().map(|part| self.check(part)).collect()
+    }
+
+    // Adds a list of values which have already been checked as a single occurrence.
+    fn push_occurrence(&mut self, values: Vec<OsString>) {
+        self.occurrences.push(values.len());
+        self.values.extend(values);
+    }
+
+    // Checks a new value against the option's registered choices and validator, if any.
+    // Returns the value to store, i.e. the matching choice if choices are registered.
+    fn check(&self, value: OsString) -> Result<OsString, Error> {
+        let mut value = value;
+
+        if !self.choices.is_empty() {
+            let string = value.to_string_lossy().into_owned();
+            let choice = self.choices.iter().find(|choice| {
+                if self.ignore_case {
+                    choice.to_lowercase() == string.to_lowercase()
+                } else {
+                    **choice == string
+                }
+            });
+            match choice {
+                Some(choice) => value = OsString::from(choice),
+                None => {
+                    let msg = format!(
+                        "'{}' is not a valid value for {} (valid choices: {})",
+                        string, self.display_name(), self.choices.join(", ")
+                    );
+                    return Err(Error::new(ErrorKind::InvalidChoice, msg).with_token(&string));
+                }
+            }
+        }
+
+        if let Some(validator) = &self.validator {
+            let string = value.to_string_lossy();
+            if let Err(message) = validator(&string) {
+                let msg = format!(
+                    "invalid value '{}' for {}: {}", string, self.display_name(), message
+                );
+                return Err(Error::new(ErrorKind::ValidationFailed, msg).with_token(&string));
+            }
+        }
+
+        Ok(value)
+    }
+
+    // Returns the placeholders for the option's values in usage form, e.g. `<arg> <arg>` or
+    // `[<arg>...]`, given the placeholder for a single value.
+    fn value_usage(&self, placeholder: &str) -> String {
+        let (min, max) = self.arity.bounds();
+        let mut parts = vec![placeholder.to_string(); min];
+        if max.is_none() {
+            match parts.last_mut() {
+                Some(last) => last.push_str("..."),
+                None => parts.push(format!("[{}...]", placeholder)),
+            }
+        }
+        parts.join(" ")
+    }
+
+    // Returns the option's name in command line form for use in error messages, preferring
+    // the first long-form alias, e.g. `--foo`.
+    fn display_name(&self) -> String {
+        display_name(&self.names)
+    }
+}
+
+
+// Returns the first long-form alias in a list of aliases in command line form, e.g. `--foo`,
+// falling back to the first single-character shortcut, e.g. `-f`.
+fn display_name(names: &[String]) -> String {
+    if let Some(name) = names.iter().find(|name| name.chars().count() > 1) {
+        return format!("--{}", name);
+    }
+    names.first().map(|name| format!("-{}", name)).unwrap_or_default()
 }
 
 
 // We create a single Flag instance for each registered flag, i.e. each call to `.flag()`.
+#[derive(Clone)]
 struct Flag {
+    names: Vec<String>,
     count: usize,
+    description: Option<String>,
+    env: Option<String>,
+    negatable: bool,
+    // The form seen last on the command line, `true` for `--foo`, `false` for `--no-foo`.
+    last_seen: Option<bool>,
+    global: bool,
+    // True for a copy of a global flag inherited from a parent parser.
+    inherited: bool,
+}
+
+
+impl Flag {
+    // Returns the flag's negated long-form aliases, e.g. `no-foo`, if it is negatable.
+    fn negations(&self) -> Vec<String> {
+        if !self.negatable {
+            return Vec::new();
+        }
+        self.names.iter()
+            .filter(|name| name.chars().count() > 1)
+            .map(|name| format!("no-{}", name))
+            .collect()
+    }
+
+    // Returns the flag's aliases for display in helptext, with long-form aliases of a
+    // negatable flag written as e.g. `[no-]foo`.
+    fn display_names(&self) -> Vec<String> {
+        self.names.iter()
+            .map(|name| if self.negatable && name.chars().count() > 1 {
+                format!("[no-]{}", name)
+            } else {
+                name.clone()
+            })
+            .collect()
+    }
 }
 
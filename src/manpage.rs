@@ -0,0 +1,211 @@
+// Support for generating man pages in roff format.
+
+use crate::ArgParser;
+use crate::globals::Inherited;
+
+
+/// A man page generated from an `ArgParser` instance.
+#[derive(Debug, Clone)]
+pub struct ManPage {
+    /// The page name, e.g. `appname` for the root parser or `appname-cmdname` for a command.
+    pub name: String,
+
+    /// The page content in roff format.
+    pub content: String,
+}
+
+
+impl ArgParser {
+    /// Returns a list of man pages in roff format, one for the parser itself followed by one
+    /// for each of its commands, including nested commands. Each page has NAME, SYNOPSIS,
+    /// ARGUMENTS, OPTIONS, and COMMANDS sections built from the registered flags, options,
+    /// commands, positionals, and descriptions. The parser's version string is included in
+    /// the page footer.
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let parser = ArgParser::new()
+    ///     .name("appname")
+    ///     .version("1.0")
+    ///     .flag("foo f");
+    ///
+    /// for page in parser.manpages() {
+    ///     println!("{}.1:\n{}", page.name, page.content);
+    /// }
+    /// ```
+    pub fn manpages(&self) -> Vec<ManPage> {
+        let name = self.app_name();
+        let mut pages = Vec::new();
+        self.collect_manpages(&[name], self.version.as_deref(), &Inherited::default(), &mut pages);
+        pages
+    }
+
+    fn collect_manpages(
+        &self,
+        path: &[String],
+        version: Option<&str>,
+        inherited: &Inherited,
+        pages: &mut Vec<ManPage>,
+    ) {
+        let page_name = path.join("-");
+        let mut out = String::new();
+
+        let footer = match version {
+            Some(version) => format!("{} {}", path[0], version.trim()),
+            None => path[0].clone(),
+        };
+        out.push_str(&format!(
+            ".TH \"{}\" \"1\" \"\" \"{}\" \"User Commands\"\n",
+            escape(&page_name.to_uppercase()), escape(&footer)
+        ));
+
+        out.push_str(".SH NAME\n");
+        match &self.description {
+            Some(description) => {
+                out.push_str(&format!("{} \\- {}\n", escape(&page_name), escape(description)))
+            }
+            None => out.push_str(&format!("{}\n", escape(&page_name))),
+        }
+
+        out.push_str(".SH SYNOPSIS\n");
+        out.push_str(&format!(".B {}\n", escape(&path.join(" "))));
+        let mut synopsis = String::from("[\\fIoptions\\fR]");
+        if !self.commands.is_empty() {
+            synopsis.push_str(" [\\fIcommand\\fR]");
+        }
+        if self.positionals.is_empty() {
+            synopsis.push_str(" [\\fIarguments\\fR]");
+        } else {
+            synopsis.push_str(&format!(" {}", escape(&self.positional_usage())));
+        }
+        out.push_str(&format!("{}\n", synopsis));
+
+        if self.positionals.iter().any(|positional| positional.description.is_some()) {
+            out.push_str(".SH ARGUMENTS\n");
+            for positional in &self.positionals {
+                if let Some(description) = &positional.description {
+                    out.push_str(".TP\n");
+                    out.push_str(&format!("\\fI{}\\fR\n", escape(&positional.name)));
+                    out.push_str(&format!("{}\n", escape(description)));
+                }
+            }
+        }
+
+        out.push_str(".SH OPTIONS\n");
+        for opt in self.options_with(inherited) {
+            let value_name = opt.value_name.as_deref().unwrap_or("arg");
+            out.push_str(".TP\n");
+            match opt.implicit_value {
+                Some(_) => {
+                    let names = format_names(&opt.names);
+                    out.push_str(&format!("{}[=\\fI{}\\fR]\n", names, escape(value_name)));
+                }
+                None => {
+                    let placeholder = format!("\\fI{}\\fR", escape(value_name));
+                    let usage = opt.value_usage(&placeholder);
+                    out.push_str(&format!("{} {}\n", format_names(&opt.names), usage));
+                }
+            }
+            let mut description = opt.description.clone().unwrap_or_default();
+            if !opt.choices.is_empty() {
+                let choices = format!("Possible values: {}.", opt.choices.join(", "));
+                append_sentence(&mut description, &choices);
+            }
+            if !opt.default.is_empty() {
+                append_sentence(&mut description, &format!("Defaults to '{}'.", opt.default));
+            }
+            out.push_str(&format!("{}\n", escape(&description)));
+        }
+        for flag in self.flags_with(inherited) {
+            out.push_str(".TP\n");
+            out.push_str(&format!("{}\n", format_names(&flag.display_names())));
+            out.push_str(&format!("{}\n", escape(flag.description.as_deref().unwrap_or(""))));
+        }
+        if self.has_helptext() {
+            let names = self.unregistered(&["h", "help"]);
+            if !names.is_empty() {
+                out.push_str(".TP\n");
+                out.push_str(&format!("{}\n", format_names(&names)));
+                out.push_str("Print the helptext and exit.\n");
+            }
+        }
+        if self.version.is_some() {
+            let names = self.unregistered(&["v", "version"]);
+            if !names.is_empty() {
+                out.push_str(".TP\n");
+                out.push_str(&format!("{}\n", format_names(&names)));
+                out.push_str("Print the version number and exit.\n");
+            }
+        }
+
+        let mut children = Vec::new();
+        if !self.commands.is_empty() {
+            out.push_str(".SH COMMANDS\n");
+            for cmd_parser in &self.commands {
+                let canonical = cmd_parser.aliases.first().cloned().unwrap_or_default();
+                let mut cmd_path = path.to_vec();
+                cmd_path.push(canonical);
+
+                let names: Vec<String> = cmd_parser.aliases.iter()
+                    .map(|alias| format!("\\fB{}\\fR", escape(alias)))
+                    .collect();
+                out.push_str(".TP\n");
+                out.push_str(&format!("{}\n", names.join(", ")));
+                if let Some(description) = &cmd_parser.description {
+                    out.push_str(&format!("{}\n", escape(description)));
+                }
+                out.push_str(&format!("See \\fB{}\\fR(1).\n", escape(&cmd_path.join("-"))));
+
+                children.push((cmd_parser, cmd_path));
+            }
+        }
+
+        pages.push(ManPage { name: page_name, content: out });
+
+        let inherited = self.inherited_by_commands(inherited);
+        for (cmd_parser, cmd_path) in children {
+            cmd_parser.collect_manpages(&cmd_path, version, &inherited, pages);
+        }
+    }
+}
+
+
+// Appends a sentence to a description.
+fn append_sentence(description: &mut String, sentence: &str) {
+    if !description.is_empty() {
+        description.push(' ');
+    }
+    description.push_str(sentence);
+}
+
+
+// Formats a list of aliases in bold with single-character shortcuts first.
+fn format_names(names: &[String]) -> String {
+    let mut shortcuts: Vec<String> = names.iter()
+        .filter(|name| name.chars().count() == 1)
+        .map(|name| format!("\\fB\\-{}\\fR", escape(name)))
+        .collect();
+    let longforms: Vec<String> = names.iter()
+        .filter(|name| name.chars().count() > 1)
+        .map(|name| format!("\\fB\\-\\-{}\\fR", escape(name)))
+        .collect();
+    shortcuts.extend(longforms);
+    shortcuts.join(", ")
+}
+
+
+// Escapes text for inclusion in a roff document.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    escaped.lines()
+        .map(|line| {
+            let line = line.trim();
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,137 @@
+// Support for declaring positional arguments by name.
+
+use crate::ArgParser;
+use crate::Error;
+use crate::ErrorKind;
+use std::ffi::OsString;
+
+
+// The number of arguments a declared positional accepts.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum PositionalKind {
+    Single,
+    Optional,
+    Variadic,
+}
+
+
+// We create a single Positional instance for each declared positional argument.
+pub(crate) struct Positional {
+    pub(crate) name: String,
+    pub(crate) kind: PositionalKind,
+    pub(crate) description: Option<String>,
+    pub(crate) values: Vec<OsString>,
+}
+
+
+impl ArgParser {
+    /// Declares a named positional argument which must be supplied exactly once. Declared
+    /// positionals are assigned arguments in the order in which they are declared and their
+    /// values can be retrieved by name using `.value()`, `.values()`, and their variants.
+    /// Parsing will fail with a `TooFewArgs` error if the argument is missing or with a
+    /// `TooManyArgs` error if there are more arguments than the declared positionals accept.
+    /// (This function will panic if a variadic positional has already been declared.)
+    ///
+    /// ```
+    /// # use arguably::ArgParser;
+    /// let mut parser = ArgParser::new()
+    ///     .positional("input")
+    ///     .optional_positional("output");
+    ///
+    /// parser.parse_vec(vec!["in.txt"]).unwrap();
+    /// assert_eq!(parser.value("input"), "in.txt");
+    /// assert!(!parser.found("output"));
+    /// ```
+    pub fn positional(self, name: &str) -> Self {
+        self.declare_positional(name, PositionalKind::Single)
+    }
+
+    /// Declares a named positional argument which can be supplied at most once. Arguments are
+    /// only assigned to optional positionals once all required positionals have a value.
+    /// (This function will panic if a variadic positional has already been declared.)
+    pub fn optional_positional(self, name: &str) -> Self {
+        self.declare_positional(name, PositionalKind::Optional)
+    }
+
+    /// Declares a named positional argument which collects any number of remaining arguments.
+    /// A variadic positional must be the last positional declared.
+    /// (This function will panic if a variadic positional has already been declared.)
+    pub fn variadic_positional(self, name: &str) -> Self {
+        self.declare_positional(name, PositionalKind::Variadic)
+    }
+
+    fn declare_positional(mut self, name: &str, kind: PositionalKind) -> Self {
+        if self.positionals.iter().any(|positional| positional.kind == PositionalKind::Variadic) {
+            panic!("'{}' cannot be declared after a variadic positional", name);
+        }
+        self.positionals.push(Positional {
+            name: name.to_string(),
+            kind,
+            description: None,
+            values: Vec::new(),
+        });
+        self
+    }
+
+    // Returns the index of the named positional, if declared.
+    pub(crate) fn positional_index(&self, name: &str) -> Option<usize> {
+        self.positionals.iter().position(|positional| positional.name == name)
+    }
+
+    // Returns the declared positionals in usage-line form, e.g. `<input> [output] [files...]`,
+    // or `[arguments]` if no positionals have been declared.
+    pub(crate) fn positional_usage(&self) -> String {
+        if self.positionals.is_empty() {
+            return "[arguments]".to_string();
+        }
+        let names: Vec<String> = self.positionals.iter()
+            .map(|positional| match positional.kind {
+                PositionalKind::Single => format!("<{}>", positional.name),
+                PositionalKind::Optional => format!("[{}]", positional.name),
+                PositionalKind::Variadic => format!("[{}...]", positional.name),
+            })
+            .collect();
+        names.join(" ")
+    }
+
+    // Assigns the positional arguments found to the declared positionals.
+    pub(crate) fn assign_positionals(&mut self) -> Result<(), Error> {
+        if self.positionals.is_empty() {
+            return Ok(());
+        }
+
+        let required = self.positionals.iter()
+            .filter(|positional| positional.kind == PositionalKind::Single)
+            .count();
+        let mut spare = self.args_os.len().saturating_sub(required);
+        let mut args = self.args_os.clone().into_iter();
+
+        for positional in &mut self.positionals {
+            positional.values.clear();
+            let take = match positional.kind {
+                PositionalKind::Single => 1,
+                PositionalKind::Optional if spare > 0 => {
+                    spare -= 1;
+                    1
+                }
+                PositionalKind::Optional => 0,
+                PositionalKind::Variadic => std::mem::take(&mut spare),
+            };
+            positional.values.extend(args.by_ref().take(take));
+            if positional.kind == PositionalKind::Single && positional.values.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::TooFewArgs,
+                    format!("missing required argument <{}>", positional.name),
+                ));
+            }
+        }
+
+        if let Some(extra) = args.next() {
+            let extra = extra.to_string_lossy();
+            let msg = format!("unexpected argument '{}'", extra);
+            return Err(Error::new(ErrorKind::TooManyArgs, msg).with_token(&extra));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+// Support for expanding response files, i.e. `@path` arguments which are replaced by the
+// arguments contained in the file at `path`.
+
+use crate::Error;
+use crate::ErrorKind;
+use std::ffi::OsString;
+use std::path::Path;
+
+
+// Response files can include other response files up to this depth.
+const MAX_DEPTH: usize = 16;
+
+
+// Expands any arguments beginning with `prefix` into the arguments contained in the named
+// file. Arguments following a `--` are left unexpanded. Each argument is returned with its
+// position in the original argument list, starting from 1; arguments read from a response
+// file take the position of the response file argument.
+pub(crate) fn expand(args: Vec<OsString>, prefix: char) -> Result<Vec<(usize, OsString)>, Error> {
+    let args = args.into_iter().enumerate().map(|(index, arg)| (index + 1, arg)).collect();
+    let mut expanded = Vec::new();
+    let mut done = false;
+    expand_into(args, prefix, 0, &mut expanded, &mut done)?;
+    Ok(expanded)
+}
+
+
+fn expand_into(
+    args: Vec<(usize, OsString)>,
+    prefix: char,
+    depth: usize,
+    expanded: &mut Vec<(usize, OsString)>,
+    done: &mut bool,
+) -> Result<(), Error> {
+    for (position, arg) in args {
+        if *done {
+            expanded.push((position, arg));
+            continue;
+        }
+        if arg == "--" {
+            *done = true;
+            expanded.push((position, arg));
+            continue;
+        }
+        match arg.to_str().and_then(|s| s.strip_prefix(prefix)) {
+            Some(path) if !path.is_empty() => {
+                if depth >= MAX_DEPTH {
+                    return Err(Error::new(ErrorKind::InvalidResponseFile, format!(
+                        "{}: response files nested more than {} levels deep", path, MAX_DEPTH
+                    )).at(position));
+                }
+                let file_args = read_file(Path::new(path)).map_err(|err| err.at(position))?;
+                let file_args = file_args.into_iter().map(|arg| (position, arg)).collect();
+                expand_into(file_args, prefix, depth + 1, expanded, done)?;
+            }
+            _ => expanded.push((position, arg)),
+        }
+    }
+    Ok(())
+}
+
+
+// Reads a response file and splits its content into arguments.
+fn read_file(path: &Path) -> Result<Vec<OsString>, Error> {
+    let text = std::fs::read_to_string(path).map_err(|err| Error::new(
+        ErrorKind::InvalidResponseFile,
+        format!("cannot read response file {}: {}", path.display(), err)
+    ))?;
+    split(&text).map_err(|(line, msg)| Error::new(ErrorKind::InvalidResponseFile,
+        format!("{}:{}: {}", path.display(), line, msg)
+    ))
+}
+
+
+// Splits text into arguments using shell-style quoting rules. Arguments are separated by
+// whitespace. Single quotes preserve their content literally. Double quotes preserve their
+// content except for backslash escapes. Outside quotes, a backslash escapes the following
+// character and a `#` at the start of an argument begins a comment which runs to the end of
+// the line. On failure, returns the line number and an error message.
+fn split(text: &str) -> Result<Vec<OsString>, (usize, String)> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut line = 1;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' | ' ' | '\t' | '\r' => {
+                if c == '\n' {
+                    line += 1;
+                }
+                if in_arg {
+                    args.push(OsString::from(std::mem::take(&mut arg)));
+                    in_arg = false;
+                }
+            }
+            '#' if !in_arg => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+            }
+            '\'' => {
+                in_arg = true;
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            arg.push(c);
+                        }
+                        None => return Err((start, "unterminated single quote".to_string())),
+                    }
+                }
+            }
+            '"' => {
+                in_arg = true;
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') | Some(c @ '$') | Some(c @ '`') => {
+                                arg.push(c);
+                            }
+                            Some('\n') => line += 1,
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err((start, "unterminated double quote".to_string())),
+                        },
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            arg.push(c);
+                        }
+                        None => return Err((start, "unterminated double quote".to_string())),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => line += 1,
+                Some(c) => {
+                    in_arg = true;
+                    arg.push(c);
+                }
+                None => return Err((line, "trailing backslash".to_string())),
+            },
+            c => {
+                in_arg = true;
+                arg.push(c);
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(OsString::from(arg));
+    }
+
+    Ok(args)
+}
@@ -0,0 +1,65 @@
+// Support for "did you mean" suggestions for unrecognised names.
+
+// We return at most this many suggestions.
+const MAX_SUGGESTIONS: usize = 3;
+
+
+// Returns the candidates closest to `input`, nearest first. A candidate is only suggested if
+// its edit distance from `input` is at most a third of the input's length (minimum 1).
+pub(crate) fn suggestions<I>(input: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let threshold = (input.chars().count() / 3).max(1);
+
+    let mut matches: Vec<(usize, String)> = candidates.into_iter()
+        .map(|candidate| (edit_distance(input, &candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+
+    matches.sort();
+    matches.dedup_by(|a, b| a.1 == b.1);
+    matches.into_iter().take(MAX_SUGGESTIONS).map(|(_, candidate)| candidate).collect()
+}
+
+
+// Formats a list of suggestions for inclusion in an error message, e.g.
+// ` (did you mean --foo or --bar?)`. Returns an empty string if the list is empty.
+pub(crate) fn format(suggestions: &[String]) -> String {
+    match suggestions.len() {
+        0 => String::new(),
+        1 => format!(" (did you mean {}?)", suggestions[0]),
+        n => format!(
+            " (did you mean {} or {}?)", suggestions[..n - 1].join(", "), suggestions[n - 1]
+        ),
+    }
+}
+
+
+// Returns the optimal string alignment distance between two strings, i.e. the number of
+// insertions, deletions, substitutions, or transpositions of adjacent characters needed to
+// turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
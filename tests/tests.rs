@@ -1,10 +1,10 @@
-use arguably::ArgParser;
+use arguably::{ArgParser, Arity, ErrorKind, Shell};
 
 #[test]
 fn flag_empty_input() {
     let mut parser = ArgParser::new().flag("flag f");
     let _ = parser.parse_vec(vec![]);
-    assert_eq!(parser.found("flag"), false);
+    assert!(!parser.found("flag"));
     assert_eq!(parser.count("flag"), 0);
 }
 
@@ -12,7 +12,7 @@ fn flag_empty_input() {
 fn flag_missing() {
     let mut parser = ArgParser::new().flag("flag f");
     let _ = parser.parse_vec(vec!["foo", "bar"]);
-    assert_eq!(parser.found("flag"), false);
+    assert!(!parser.found("flag"));
     assert_eq!(parser.count("flag"), 0);
 }
 
@@ -20,7 +20,7 @@ fn flag_missing() {
 fn flag_present_long() {
     let mut parser = ArgParser::new().flag("flag f");
     let _ = parser.parse_vec(vec!["--flag"]);
-    assert_eq!(parser.found("flag"), true);
+    assert!(parser.found("flag"));
     assert_eq!(parser.count("flag"), 1);
 }
 
@@ -28,7 +28,7 @@ fn flag_present_long() {
 fn flag_present_short() {
     let mut parser = ArgParser::new().flag("flag f");
     let _ = parser.parse_vec(vec!["-f"]);
-    assert_eq!(parser.found("flag"), true);
+    assert!(parser.found("flag"));
     assert_eq!(parser.count("flag"), 1);
 }
 
@@ -36,7 +36,7 @@ fn flag_present_short() {
 fn flag_multiple_long() {
     let mut parser = ArgParser::new().flag("flag f");
     let _ = parser.parse_vec(vec!["--flag", "--flag", "--flag"]);
-    assert_eq!(parser.found("flag"), true);
+    assert!(parser.found("flag"));
     assert_eq!(parser.count("flag"), 3);
 }
 
@@ -44,7 +44,7 @@ fn flag_multiple_long() {
 fn flag_multiple_short() {
     let mut parser = ArgParser::new().flag("flag f");
     let _ = parser.parse_vec(vec!["-f", "-f", "-f"]);
-    assert_eq!(parser.found("flag"), true);
+    assert!(parser.found("flag"));
     assert_eq!(parser.count("flag"), 3);
 }
 
@@ -52,7 +52,7 @@ fn flag_multiple_short() {
 fn flag_multiple_mixed() {
     let mut parser = ArgParser::new().flag("flag f");
     let _ = parser.parse_vec(vec!["--flag", "-f", "-f"]);
-    assert_eq!(parser.found("flag"), true);
+    assert!(parser.found("flag"));
     assert_eq!(parser.count("flag"), 3);
 }
 
@@ -60,7 +60,7 @@ fn flag_multiple_mixed() {
 fn flag_multiple_condensed() {
     let mut parser = ArgParser::new().flag("flag f");
     let _ = parser.parse_vec(vec!["-fff", "--flag"]);
-    assert_eq!(parser.found("flag"), true);
+    assert!(parser.found("flag"));
     assert_eq!(parser.count("flag"), 4);
 }
 
@@ -68,7 +68,7 @@ fn flag_multiple_condensed() {
 fn option_empty_input() {
     let mut parser = ArgParser::new().option("opt o", "default");
     let _ = parser.parse_vec(vec![]);
-    assert_eq!(parser.found("opt"), false);
+    assert!(!parser.found("opt"));
     assert!(parser.value("opt") == "default");
 }
 
@@ -76,7 +76,7 @@ fn option_empty_input() {
 fn option_missing() {
     let mut parser = ArgParser::new().option("opt o", "default");
     let _ = parser.parse_vec(vec!["foo", "bar"]);
-    assert_eq!(parser.found("opt"), false);
+    assert!(!parser.found("opt"));
     assert!(parser.value("opt") == "default");
 }
 
@@ -84,7 +84,7 @@ fn option_missing() {
 fn option_present_long() {
     let mut parser = ArgParser::new().option("opt o", "default");
     let _ = parser.parse_vec(vec!["--opt", "foo"]);
-    assert_eq!(parser.found("opt"), true);
+    assert!(parser.found("opt"));
     assert!(parser.value("opt") == "foo");
 }
 
@@ -92,7 +92,7 @@ fn option_present_long() {
 fn option_present_short() {
     let mut parser = ArgParser::new().option("opt o", "default");
     let _ = parser.parse_vec(vec!["-o", "foo"]);
-    assert_eq!(parser.found("opt"), true);
+    assert!(parser.found("opt"));
     assert!(parser.value("opt") == "foo");
 }
 
@@ -100,7 +100,7 @@ fn option_present_short() {
 fn option_multiple_values() {
     let mut parser = ArgParser::new().option("opt o", "default");
     let _ = parser.parse_vec(vec!["-o", "foo", "--opt", "bar"]);
-    assert_eq!(parser.found("opt"), true);
+    assert!(parser.found("opt"));
     assert_eq!(parser.count("opt"), 2);
     assert_eq!(parser.value("opt"), "bar");
     assert_eq!(parser.values("opt"), vec!["foo", "bar"]);
@@ -134,3 +134,1235 @@ fn arguments_found() {
     assert_eq!(parser.args, vec!["foo", "bar"]);
 }
 
+
+#[test]
+fn option_value_as_int() {
+    let mut parser = ArgParser::new().option("opt o", "1");
+    let _ = parser.parse_vec(vec!["--opt", "123"]);
+    assert_eq!(parser.value_as::<u32>("opt").unwrap(), 123);
+}
+
+#[test]
+fn option_value_as_default() {
+    let parser = ArgParser::new().option("opt o", "1");
+    assert_eq!(parser.value_as::<u32>("opt").unwrap(), 1);
+}
+
+#[test]
+fn option_value_as_invalid() {
+    let mut parser = ArgParser::new().option("opt o", "1");
+    let _ = parser.parse_vec(vec!["--opt", "abc"]);
+    assert_eq!(parser.value_as::<u32>("opt").unwrap_err().kind(), ErrorKind::InvalidValue);
+    let err = parser.values_as::<u32>("o").unwrap_err();
+    assert_eq!(err.message(), "'abc' is not a valid value for --opt");
+}
+
+#[test]
+fn option_values_as_int() {
+    let mut parser = ArgParser::new().option("opt o", "1");
+    let _ = parser.parse_vec(vec!["-o", "1", "-o", "2"]);
+    assert_eq!(parser.values_as::<u32>("opt").unwrap(), vec![1, 2]);
+}
+
+#[test]
+fn arguments_arg_as() {
+    let mut parser = ArgParser::new();
+    let _ = parser.parse_vec(vec!["1.5", "foo"]);
+    assert_eq!(parser.arg_as::<f64>(0).unwrap(), 1.5);
+    assert_eq!(parser.arg_as::<f64>(1).unwrap_err().kind(), ErrorKind::InvalidValue);
+    assert_eq!(parser.arg_as::<f64>(2).unwrap_err().kind(), ErrorKind::MissingValue);
+}
+
+#[test]
+fn helptext_generated() {
+    let parser = ArgParser::new()
+        .name("app")
+        .helptext("Header text.")
+        .footer("Footer text.")
+        .generate_helptext(true)
+        .option("opt o", "default")
+        .describe("opt", "An option.")
+        .value_name("opt", "value")
+        .flag("flag f")
+        .describe("f", "A flag.")
+        .command("cmd", ArgParser::new().helptext("Usage: app cmd"))
+        .describe("cmd", "A command.");
+    let helptext = parser.render_helptext();
+    assert!(helptext.starts_with("Header text.\n\nUsage: app [options] [command]"));
+    assert!(helptext.contains("  -o, --opt <value>    An option. [default: default]\n"));
+    assert!(helptext.contains("  -f, --flag           A flag.\n"));
+    assert!(helptext.contains("  -h, --help           Print this helptext and exit.\n"));
+    assert!(helptext.contains("Commands:\n  cmd"));
+    assert!(helptext.contains("A command."));
+    assert!(helptext.ends_with("\n\nFooter text."));
+}
+
+#[test]
+fn helptext_manual() {
+    let parser = ArgParser::new().helptext("Usage: app...").flag("flag f");
+    assert_eq!(parser.render_helptext(), "Usage: app...");
+}
+
+#[test]
+fn completion_script_bash() {
+    let parser = ArgParser::new()
+        .name("app")
+        .flag("flag f")
+        .option("opt o", "")
+        .command("cmd c", ArgParser::new().flag("cmdflag"));
+    let script = parser.completion_script(Shell::Bash);
+    assert!(script.contains("app) words=\"--flag -f --opt -o cmd c\" ;;"));
+    assert!(script.contains("app:cmd|app:c) cmdpath=\"app_cmd\" ;;"));
+    assert!(script.contains("app_cmd) words=\"--cmdflag\" ;;"));
+    assert!(script.contains("complete -o default -F _app app"));
+}
+
+#[test]
+fn completion_script_zsh() {
+    let parser = ArgParser::new()
+        .name("app")
+        .flag("flag f")
+        .command("cmd", ArgParser::new().flag("cmdflag"));
+    let script = parser.completion_script(Shell::Zsh);
+    assert!(script.starts_with("#compdef app\n"));
+    assert!(script.contains("(app_cmd) compadd -- --cmdflag ;;"));
+}
+
+#[test]
+fn completion_script_fish() {
+    let parser = ArgParser::new()
+        .name("app")
+        .flag("flag f")
+        .describe("flag", "It's a flag.")
+        .command("cmd", ArgParser::new().flag("cmdflag"));
+    let script = parser.completion_script(Shell::Fish);
+    assert!(script.contains("complete -c app -n 'test (__app_cmdpath) = app' -l flag -s f -d 'It\\'s a flag.'"));
+    assert!(script.contains("complete -c app -n 'test (__app_cmdpath) = app_cmd' -l cmdflag"));
+}
+
+#[test]
+fn manpages_generated() {
+    let parser = ArgParser::new()
+        .name("app")
+        .version("1.2.3")
+        .option("opt o", "")
+        .value_name("opt", "file")
+        .flag("flag f")
+        .describe("flag", "Enables the flag.")
+        .command("cmd c", ArgParser::new().flag("cmdflag"))
+        .describe("cmd", "Runs the command.");
+    let pages = parser.manpages();
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[0].name, "app");
+    assert_eq!(pages[1].name, "app-cmd");
+    assert!(pages[0].content.starts_with(".TH \"APP\" \"1\" \"\" \"app 1.2.3\""));
+    assert!(pages[0].content.contains(".SH SYNOPSIS\n.B app\n"));
+    assert!(pages[0].content.contains("\\fB\\-o\\fR, \\fB\\-\\-opt\\fR \\fIfile\\fR\n"));
+    assert!(pages[0].content.contains("\\fB\\-f\\fR, \\fB\\-\\-flag\\fR\nEnables the flag.\n"));
+    assert!(pages[0].content.contains(".SH COMMANDS\n.TP\n\\fBcmd\\fR, \\fBc\\fR\nRuns the command.\n"));
+    assert!(pages[1].content.contains(".SH NAME\napp\\-cmd \\- Runs the command.\n"));
+    assert!(pages[1].content.contains(".B app cmd\n"));
+}
+
+#[cfg(unix)]
+fn invalid_unicode(prefix: &str) -> std::ffi::OsString {
+    use std::os::unix::ffi::OsStringExt;
+    let mut bytes = prefix.as_bytes().to_vec();
+    bytes.push(0xff);
+    std::ffi::OsString::from_vec(bytes)
+}
+
+#[cfg(unix)]
+#[test]
+fn unicode_invalid_rejected() {
+    let mut parser = ArgParser::new();
+    let args = vec!["foo".into(), invalid_unicode("bar")];
+    let err = parser.parse_os_vec(args).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidUnicode);
+    assert_eq!(err.index(), Some(2));
+}
+
+#[cfg(unix)]
+#[test]
+fn unicode_invalid_allowed() {
+    let mut parser = ArgParser::new().allow_invalid_unicode(true).option("opt o", "");
+    let args = vec![
+        "--opt".into(), invalid_unicode("foo"),
+        invalid_unicode("--opt=bar"),
+        invalid_unicode("baz"),
+    ];
+    assert!(parser.parse_os_vec(args).is_ok());
+    assert_eq!(parser.values_os("opt"), vec![invalid_unicode("foo"), invalid_unicode("bar")]);
+    assert_eq!(parser.value_os("opt"), invalid_unicode("bar"));
+    assert_eq!(parser.value("opt"), "bar\u{FFFD}");
+    assert_eq!(parser.args_os(), vec![invalid_unicode("baz")]);
+    assert_eq!(parser.args, vec!["baz\u{FFFD}"]);
+}
+
+#[test]
+fn help_flag_returned() {
+    let mut parser = ArgParser::new().helptext("Usage: app").exit_on_help(false);
+    match parser.parse_vec(vec!["--help"]) {
+        Err(err) if err.kind() == ErrorKind::HelpRequested => assert_eq!(err.message(), "Usage: app"),
+        _ => panic!("expected HelpRequested"),
+    }
+    assert_eq!(parser.parse_vec(vec!["-h"]).unwrap_err().kind(), ErrorKind::HelpRequested);
+}
+
+#[test]
+fn version_flag_returned() {
+    let mut parser = ArgParser::new().version("1.0").exit_on_help(false);
+    match parser.parse_vec(vec!["-v"]) {
+        Err(err) if err.kind() == ErrorKind::VersionRequested => assert_eq!(err.message(), "1.0"),
+        _ => panic!("expected VersionRequested"),
+    }
+}
+
+#[test]
+fn help_command_returned() {
+    let mut parser = ArgParser::new()
+        .exit_on_help(false)
+        .command("cmd", ArgParser::new().helptext("Usage: app cmd"));
+    match parser.parse_vec(vec!["help", "cmd"]) {
+        Err(err) if err.kind() == ErrorKind::HelpRequested => assert_eq!(err.message(), "Usage: app cmd"),
+        _ => panic!("expected HelpRequested"),
+    }
+}
+
+#[test]
+fn completion_command_returned() {
+    let mut parser = ArgParser::new()
+        .name("app")
+        .exit_on_help(false)
+        .enable_completion_command(true);
+    match parser.parse_vec(vec!["completion", "fish"]) {
+        Err(err) if err.kind() == ErrorKind::CompletionRequested => assert!(err.message().contains("function __app_cmdpath")),
+        _ => panic!("expected CompletionRequested"),
+    }
+}
+
+#[test]
+fn option_required_missing() {
+    let mut parser = ArgParser::new().option("opt o", "").required("opt");
+    match parser.parse_vec(vec!["foo"]) {
+        Err(err) if err.kind() == ErrorKind::MissingRequired => assert_eq!(err.message(), "missing required option --opt"),
+        _ => panic!("expected MissingRequired"),
+    }
+}
+
+#[test]
+fn option_required_present() {
+    let mut parser = ArgParser::new().option("opt o", "").required("opt");
+    assert!(parser.parse_vec(vec!["-o", "foo"]).is_ok());
+}
+
+#[test]
+fn arguments_min_max() {
+    let mut parser = ArgParser::new().min_args(1).max_args(2);
+    assert_eq!(parser.parse_vec(vec![]).unwrap_err().kind(), ErrorKind::TooFewArgs);
+    let mut parser = ArgParser::new().min_args(1).max_args(2);
+    assert_eq!(parser.parse_vec(vec!["a", "b", "c"]).unwrap_err().kind(), ErrorKind::TooManyArgs);
+    let mut parser = ArgParser::new().min_args(1).max_args(2);
+    assert!(parser.parse_vec(vec!["a", "b"]).is_ok());
+}
+
+#[test]
+fn command_required_option() {
+    let mut parser = ArgParser::new()
+        .command("cmd", ArgParser::new().option("opt", "").required("opt"));
+    assert_eq!(parser.parse_vec(vec!["cmd"]).unwrap_err().kind(), ErrorKind::MissingRequired);
+}
+
+fn test_env(var: &str) -> Option<String> {
+    match var {
+        "TEST_OPT" => Some("env".to_string()),
+        "TEST_FLAG" => Some("yes".to_string()),
+        "TEST_BAD_FLAG" => Some("maybe".to_string()),
+        _ => None,
+    }
+}
+
+#[test]
+fn env_option_fallback() {
+    let mut parser = ArgParser::new()
+        .option("opt o", "default")
+        .env("opt", "TEST_OPT")
+        .env_source(test_env);
+    let _ = parser.parse_vec(vec![]);
+    assert_eq!(parser.value("opt"), "env");
+}
+
+#[test]
+fn env_option_overridden() {
+    let mut parser = ArgParser::new()
+        .option("opt o", "default")
+        .env("opt", "TEST_OPT")
+        .env_source(test_env);
+    let _ = parser.parse_vec(vec!["--opt", "cli"]);
+    assert_eq!(parser.value("opt"), "cli");
+}
+
+#[test]
+fn env_flag_fallback() {
+    let mut parser = ArgParser::new()
+        .flag("flag f")
+        .env("flag", "TEST_FLAG")
+        .command("cmd", ArgParser::new().flag("cmdflag").env("cmdflag", "TEST_FLAG"))
+        .env_source(test_env);
+    let _ = parser.parse_vec(vec!["cmd"]);
+    assert!(parser.found("flag"));
+    assert!(parser.cmd_parser.unwrap().found("cmdflag"));
+}
+
+#[test]
+fn env_flag_invalid() {
+    let mut parser = ArgParser::new()
+        .flag("flag f")
+        .env("flag", "TEST_BAD_FLAG")
+        .env_source(test_env);
+    assert_eq!(parser.parse_vec(vec![]).unwrap_err().kind(), ErrorKind::InvalidValue);
+}
+
+fn write_temp_file(name: &str, content: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("arguably-{}-{}", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn config_file_values() {
+    let system = write_temp_file("system.conf", "opt = system\nflag = true\n[cmd]\ncmdopt = 'cmd value'\n");
+    let user = write_temp_file("user.conf", "# comment\nopt = user\n");
+    let mut parser = ArgParser::new()
+        .option("opt o", "default")
+        .flag("flag f")
+        .config_file(&system)
+        .config_file(&user)
+        .config_file("/nonexistent/arguably.conf")
+        .command("cmd", ArgParser::new().option("cmdopt", ""));
+    assert!(parser.parse_vec(vec!["cmd"]).is_ok());
+    assert_eq!(parser.value("opt"), "user");
+    assert!(parser.found("flag"));
+    assert_eq!(parser.cmd_parser.unwrap().value("cmdopt"), "cmd value");
+}
+
+#[test]
+fn config_file_precedence() {
+    let path = write_temp_file("precedence.conf", "opt = file\nother = file\nflag = true\n");
+    let mut parser = ArgParser::new()
+        .option("opt o", "default")
+        .option("other", "default")
+        .env("other", "TEST_OPT")
+        .flag("flag f")
+        .env("flag", "TEST_FLAG_OFF")
+        .env_source(|var| match var {
+            "TEST_OPT" => Some("env".to_string()),
+            "TEST_FLAG_OFF" => Some("0".to_string()),
+            _ => None,
+        })
+        .config_file(&path);
+    assert!(parser.parse_vec(vec!["--opt", "cli"]).is_ok());
+    assert_eq!(parser.value("opt"), "cli");
+    assert_eq!(parser.value("other"), "env");
+    assert!(!parser.found("flag"));
+}
+
+#[test]
+fn config_option_loaded() {
+    let path = write_temp_file("option.conf", "opt = file\n");
+    let mut parser = ArgParser::new()
+        .option("opt o", "default")
+        .option("config", "")
+        .config_option("config");
+    assert!(parser.parse_vec(vec!["--config", path.to_str().unwrap()]).is_ok());
+    assert_eq!(parser.value("opt"), "file");
+}
+
+#[test]
+fn config_file_invalid() {
+    let path = write_temp_file("invalid.conf", "opt = file\nnonsense\n");
+    let mut parser = ArgParser::new().option("opt o", "default").config_file(&path);
+    match parser.parse_vec(vec![]) {
+        Err(err) if err.kind() == ErrorKind::InvalidConfig => assert!(err.message().ends_with("invalid.conf:2: expected 'key = value'")),
+        _ => panic!("expected InvalidConfig"),
+    }
+}
+
+#[test]
+fn response_file_expanded() {
+    let nested = write_temp_file("nested.rsp", "nested 'single quoted'\n");
+    let path = write_temp_file(
+        "args.rsp",
+        &format!("--opt \"double \\\"quoted\\\"\" # comment\nfoo\\ bar @{}\n", nested.display()),
+    );
+    let mut parser = ArgParser::new().option("opt o", "").response_files(true);
+    let arg = format!("@{}", path.display());
+    assert!(parser.parse_vec(vec![&arg, "after", "--", "@literal"]).is_ok());
+    assert_eq!(parser.value("opt"), "double \"quoted\"");
+    assert_eq!(parser.args, vec!["foo bar", "nested", "single quoted", "after", "@literal"]);
+}
+
+#[test]
+fn response_file_custom_prefix() {
+    let path = write_temp_file("prefix.rsp", "foo bar");
+    let mut parser = ArgParser::new().response_file_prefix('+');
+    let arg = format!("+{}", path.display());
+    assert!(parser.parse_vec(vec![&arg, "@foo"]).is_ok());
+    assert_eq!(parser.args, vec!["foo", "bar", "@foo"]);
+}
+
+#[test]
+fn response_file_errors() {
+    let path = write_temp_file("unterminated.rsp", "foo\nbar 'baz\n");
+    let mut parser = ArgParser::new().response_files(true);
+    let arg = format!("@{}", path.display());
+    match parser.parse_vec(vec![&arg]) {
+        Err(err) if err.kind() == ErrorKind::InvalidResponseFile => assert!(err.message().ends_with("unterminated.rsp:2: unterminated single quote")),
+        _ => panic!("expected InvalidResponseFile"),
+    }
+
+    let path = write_temp_file("recursive.rsp", "");
+    std::fs::write(&path, format!("@{}", path.display())).unwrap();
+    let arg = format!("@{}", path.display());
+    let mut parser = ArgParser::new().response_files(true);
+    assert_eq!(parser.parse_vec(vec![&arg]).unwrap_err().kind(), ErrorKind::InvalidResponseFile);
+}
+
+#[test]
+fn constraint_conflicts() {
+    let parser = || ArgParser::new().flag("json").flag("csv").option("xml", "").conflicts(&["json", "csv", "xml"]);
+    assert!(parser().parse_vec(vec!["--json"]).is_ok());
+    match parser().parse_vec(vec!["--json", "--xml", "foo"]) {
+        Err(err) if err.kind() == ErrorKind::Conflict => assert_eq!(err.message(), "--json and --xml cannot be used together"),
+        _ => panic!("expected Conflict"),
+    }
+}
+
+#[test]
+fn constraint_at_least_one() {
+    let parser = || ArgParser::new().option("file", "").option("url", "").at_least_one(&["file", "url"]);
+    assert!(parser().parse_vec(vec!["--file", "a", "--url", "b"]).is_ok());
+    match parser().parse_vec(vec![]) {
+        Err(err) if err.kind() == ErrorKind::MissingRequired => assert_eq!(err.message(), "at least one of --file or --url is required"),
+        _ => panic!("expected MissingRequired"),
+    }
+}
+
+#[test]
+fn constraint_exactly_one() {
+    let parser = || ArgParser::new().flag("a").flag("b").flag("c").exactly_one(&["a", "b", "c"]);
+    assert!(parser().parse_vec(vec!["--b"]).is_ok());
+    assert_eq!(parser().parse_vec(vec!["--a", "--c"]).unwrap_err().kind(), ErrorKind::Conflict);
+    match parser().parse_vec(vec![]) {
+        Err(err) if err.kind() == ErrorKind::MissingRequired => assert_eq!(err.message(), "one of -a, -b, or -c is required"),
+        _ => panic!("expected MissingRequired"),
+    }
+}
+
+#[test]
+fn constraint_requires() {
+    let parser = || ArgParser::new().option("user u", "").option("password p", "").requires("u", "password");
+    assert!(parser().parse_vec(vec![]).is_ok());
+    assert!(parser().parse_vec(vec!["-u", "me", "-p", "secret"]).is_ok());
+    match parser().parse_vec(vec!["-u", "me"]) {
+        Err(err) if err.kind() == ErrorKind::MissingRequired => assert_eq!(err.message(), "--user requires --password"),
+        _ => panic!("expected MissingRequired"),
+    }
+}
+
+#[test]
+fn option_choices() {
+    let parser = || ArgParser::new().option("format f", "json").choices("format", &["json", "csv"]);
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["-f", "csv"]).is_ok());
+    assert_eq!(p.value("format"), "csv");
+    match parser().parse_vec(vec!["--format=CSV"]) {
+        Err(err) if err.kind() == ErrorKind::InvalidChoice => assert_eq!(err.message(), "'CSV' is not a valid value for --format (valid choices: json, csv)"),
+        _ => panic!("expected InvalidChoice"),
+    }
+}
+
+#[test]
+fn option_choices_ignore_case() {
+    let mut parser = ArgParser::new().option("format f", "json").choices_ignore_case("format", &["json", "csv"]);
+    assert!(parser.parse_vec(vec!["-f", "CSV"]).is_ok());
+    assert_eq!(parser.value("format"), "csv");
+}
+
+#[test]
+fn option_choices_in_help_and_completion() {
+    let parser = ArgParser::new()
+        .name("app")
+        .generate_helptext(true)
+        .option("format f", "")
+        .choices("format", &["json", "csv"]);
+    assert!(parser.render_helptext().contains("[choices: json, csv]"));
+    assert!(parser.completion_script(Shell::Bash).contains("compgen -W \"json csv\""));
+    assert!(parser.completion_script(Shell::Fish).contains("-f -a 'json csv'"));
+}
+
+#[test]
+fn option_validator() {
+    let parser = || ArgParser::new()
+        .option("port p", "8080")
+        .validator("port", |value| match value.parse::<u16>() {
+            Ok(port) if port >= 1024 => Ok(()),
+            _ => Err("expected a port number above 1023".to_string()),
+        });
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["-p", "8000"]).is_ok());
+    assert_eq!(p.value("port"), "8000");
+    let err = parser().parse_vec(vec!["-p", "80"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ValidationFailed);
+    assert_eq!(err.token(), Some("80"));
+    assert_eq!(err.message(), "invalid value '80' for --port: expected a port number above 1023");
+}
+
+#[test]
+fn option_validator_env() {
+    let mut parser = ArgParser::new()
+        .option("opt o", "")
+        .env("opt", "TEST_OPT")
+        .env_source(test_env)
+        .validator("opt", |value| if value == "env" { Err("no".to_string()) } else { Ok(()) });
+    assert_eq!(parser.parse_vec(vec![]).unwrap_err().kind(), ErrorKind::ValidationFailed);
+}
+
+#[test]
+fn suggest_long_names() {
+    let parser = || ArgParser::new()
+        .flag("verbose v")
+        .flag("version-info")
+        .option("output o", "");
+    let err = parser().parse_vec(vec!["--verbsoe"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidName);
+    assert_eq!(err.suggestions(), ["--verbose"]);
+    assert_eq!(err.message(), "--verbsoe is not a recognised flag or option name (did you mean --verbose?)");
+    assert_eq!(parser().parse_vec(vec!["--ouptut=foo"]).unwrap_err().suggestions(), ["--output"]);
+    assert_eq!(parser().parse_vec(vec!["-output", "foo"]).unwrap_err().suggestions(), ["--output"]);
+    let err = parser().parse_vec(vec!["--xyz"]).unwrap_err();
+    assert!(err.suggestions().is_empty());
+    assert_eq!(err.message(), "--xyz is not a recognised flag or option name");
+}
+
+#[test]
+fn suggest_command_names() {
+    let parser = || ArgParser::new()
+        .max_args(0)
+        .enable_help_command(true)
+        .command("build b", ArgParser::new())
+        .command("bench", ArgParser::new());
+    let err = parser().parse_vec(vec!["biuld"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidName);
+    assert_eq!(err.suggestions(), ["build"]);
+    assert_eq!(err.message(), "'biuld' is not a recognised command name (did you mean build?)");
+    assert_eq!(parser().parse_vec(vec!["help", "bnech"]).unwrap_err().suggestions(), ["bench"]);
+    assert!(ArgParser::new().command("build", ArgParser::new()).parse_vec(vec!["biuld"]).is_ok());
+}
+
+#[test]
+fn error_location() {
+    let mut parser = ArgParser::new()
+        .command("build b", ArgParser::new()
+            .option("jobs j", "1")
+            .choices("jobs", &["1", "2"])
+        );
+    let err = parser.parse_vec(vec!["b", "-j", "2", "--jobs", "3"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidChoice);
+    assert_eq!(err.token(), Some("3"));
+    assert_eq!(err.index(), Some(5));
+    assert_eq!(err.command_path(), ["build"]);
+    assert_eq!(err.exit_code(), 64);
+
+    let err = ArgParser::new().parse_vec(vec!["--bar"]).unwrap_err();
+    assert_eq!(err.token(), Some("--bar"));
+    assert_eq!(err.index(), Some(1));
+    assert!(err.command_path().is_empty());
+    assert_eq!(err.to_string(), "Error: --bar is not a recognised flag or option name");
+}
+
+#[test]
+fn error_location_response_file() {
+    let path = write_temp_file("location.rsp", "--foo\n--bar");
+    let arg = format!("@{}", path.display());
+    let mut parser = ArgParser::new()
+        .response_files(true)
+        .flag("foo");
+    let err = parser.parse_vec(vec!["x", &arg]).unwrap_err();
+    assert_eq!(err.token(), Some("--bar"));
+    assert_eq!(err.index(), Some(2));
+}
+
+#[test]
+fn error_exit_codes() {
+    let mut parser = ArgParser::new()
+        .version("1.0")
+        .exit_on_help(false);
+    assert_eq!(parser.parse_vec(vec!["--version"]).unwrap_err().exit_code(), 0);
+    assert_eq!(parser.parse_vec(vec!["--foo"]).unwrap_err().exit_code(), 64);
+}
+
+#[test]
+fn command_callback_closure() {
+    let found = std::rc::Rc::new(std::cell::Cell::new(false));
+    let spy = found.clone();
+    let mut parser = ArgParser::new()
+        .command("cmd", ArgParser::new()
+            .callback(move |name, _| {
+                assert_eq!(name, "cmd");
+                spy.set(true);
+            })
+        );
+    assert!(parser.parse_vec(vec!["cmd"]).is_ok());
+    assert!(found.get());
+}
+
+#[test]
+fn command_handler_errors() {
+    let mut parser = ArgParser::new()
+        .command("cmd", ArgParser::new()
+            .option("num", "x")
+            .handler(|_, parser| {
+                parser.value_as::<u32>("num")?;
+                Ok(())
+            })
+        );
+    let err = parser.parse_vec(vec!["cmd"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    assert_eq!(err.command_path(), ["cmd"]);
+
+    let mut parser = ArgParser::new()
+        .command("cmd", ArgParser::new()
+            .handler(|_, _| Err("database unavailable".into()))
+        );
+    let err = parser.parse_vec(vec!["cmd"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::HandlerFailed);
+    assert_eq!(err.to_string(), "Error: database unavailable");
+    assert!(std::error::Error::source(&err).is_some());
+    assert_eq!(err.exit_code(), 1);
+}
+
+#[test]
+fn command_handler_context() {
+    let mut parser = ArgParser::new()
+        .command("push", ArgParser::new()
+            .command("item", ArgParser::new()
+                .handler_with(|_, parser, items: &mut Vec<String>| {
+                    items.extend(parser.args.iter().cloned());
+                    Ok(())
+                })
+            )
+        );
+    let mut items: Vec<String> = Vec::new();
+    assert!(parser.parse_vec_with(vec!["push", "item", "a", "b"], &mut items).is_ok());
+    assert_eq!(items, vec!["a", "b"]);
+}
+
+#[test]
+fn command_handler_context_mismatch() {
+    let parser = || ArgParser::new()
+        .command("add", ArgParser::new()
+            .handler_with(|_, _, total: &mut i32| {
+                *total += 1;
+                Ok(())
+            })
+        );
+    let err = parser().parse_vec(vec!["add"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::HandlerFailed);
+    assert_eq!(err.message(), "the handler for 'add' expects a context of type i32");
+    assert_eq!(err.command_path(), ["add"]);
+    let mut total = 0u8;
+    assert_eq!(parser().parse_vec_with(vec!["add"], &mut total).unwrap_err().kind(), ErrorKind::HandlerFailed);
+}
+
+#[test]
+fn positional_named() {
+    let parser = || ArgParser::new()
+        .positional("input")
+        .optional_positional("output")
+        .variadic_positional("extras");
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["a"]).is_ok());
+    assert_eq!(p.value("input"), "a");
+    assert_eq!(p.value("output"), "");
+    assert!(!p.found("output"));
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["a", "b", "c", "d"]).is_ok());
+    assert_eq!(p.value("output"), "b");
+    assert_eq!(p.values("extras"), vec!["c", "d"]);
+    assert_eq!(p.count("extras"), 2);
+    assert_eq!(p.args, vec!["a", "b", "c", "d"]);
+}
+
+#[test]
+fn positional_optional_before_required() {
+    let parser = || ArgParser::new()
+        .optional_positional("src")
+        .positional("dst");
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["x"]).is_ok());
+    assert!(!p.found("src"));
+    assert_eq!(p.value("dst"), "x");
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["x", "y"]).is_ok());
+    assert_eq!(p.value("src"), "x");
+    assert_eq!(p.value("dst"), "y");
+}
+
+#[test]
+fn positional_errors() {
+    let parser = || ArgParser::new()
+        .positional("input")
+        .optional_positional("output");
+    let err = parser().parse_vec(vec![]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TooFewArgs);
+    assert_eq!(err.message(), "missing required argument <input>");
+    let err = parser().parse_vec(vec!["a", "b", "c"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TooManyArgs);
+    assert_eq!(err.message(), "unexpected argument 'c'");
+    assert_eq!(err.token(), Some("c"));
+}
+
+#[test]
+fn positional_helptext() {
+    let parser = ArgParser::new()
+        .name("app")
+        .generate_helptext(true)
+        .positional("input")
+        .optional_positional("output")
+        .variadic_positional("files")
+        .describe("input", "The input file.");
+    let helptext = parser.render_helptext();
+    assert!(helptext.contains("Usage: app [options] <input> [output] [files...]"));
+    assert!(helptext.contains("Arguments:\n  input    The input file."));
+    assert!(parser.manpages()[0].content.contains(".SH ARGUMENTS"));
+}
+
+#[test]
+#[should_panic]
+fn positional_after_variadic() {
+    let _ = ArgParser::new()
+        .variadic_positional("files")
+        .positional("output");
+}
+
+#[test]
+fn option_implicit_value() {
+    let parser = || ArgParser::new()
+        .option("color c", "auto")
+        .implicit_value("color", "always");
+    let mut p = parser();
+    assert!(p.parse_vec(vec![]).is_ok());
+    assert_eq!(p.value("color"), "auto");
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--color", "foo"]).is_ok());
+    assert_eq!(p.value("color"), "always");
+    assert_eq!(p.args, vec!["foo"]);
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["-c", "foo"]).is_ok());
+    assert_eq!(p.value("color"), "always");
+    assert_eq!(p.args, vec!["foo"]);
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--color=never"]).is_ok());
+    assert_eq!(p.value("color"), "never");
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["-c=never"]).is_ok());
+    assert_eq!(p.value("color"), "never");
+}
+
+#[test]
+fn option_implicit_value_helptext() {
+    let parser = ArgParser::new()
+        .name("app")
+        .generate_helptext(true)
+        .option("color c", "")
+        .value_name("color", "when")
+        .implicit_value("color", "always");
+    assert!(parser.render_helptext().contains("-c, --color[=<when>]"));
+    let script = parser.completion_script(Shell::Bash);
+    assert!(script.contains("--color -c"));
+    assert!(!script.contains(":--color|"));
+}
+
+#[test]
+fn flag_negatable() {
+    let parser = || ArgParser::new()
+        .flag("cache c")
+        .negatable("cache");
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--cache", "--no-cache"]).is_ok());
+    assert!(!p.enabled("cache"));
+    assert!(p.negated("cache"));
+    assert_eq!(p.count("cache"), 1);
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--no-cache", "-c"]).is_ok());
+    assert!(p.enabled("c"));
+    assert!(!p.negated("cache"));
+    let mut p = parser();
+    assert!(p.parse_vec(vec![]).is_ok());
+    assert!(!p.enabled("cache"));
+    assert!(!p.negated("cache"));
+    assert!(ArgParser::new().flag("cache").parse_vec(vec!["--no-cache"]).is_err());
+}
+
+#[test]
+fn flag_negatable_overrides_env() {
+    let parser = || ArgParser::new()
+        .flag("flag")
+        .negatable("flag")
+        .env("flag", "TEST_FLAG")
+        .env_source(test_env);
+    let mut p = parser();
+    assert!(p.parse_vec(vec![]).is_ok());
+    assert!(p.enabled("flag"));
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--no-flag"]).is_ok());
+    assert!(!p.enabled("flag"));
+}
+
+#[test]
+fn flag_negatable_helptext() {
+    let parser = ArgParser::new()
+        .name("app")
+        .generate_helptext(true)
+        .flag("cache c")
+        .negatable("cache");
+    assert!(parser.render_helptext().contains("-c, --[no-]cache"));
+    assert!(parser.completion_script(Shell::Bash).contains("--no-cache"));
+    let err = ArgParser::new().flag("cache").negatable("cache").parse_vec(vec!["--no-cahce"]).unwrap_err();
+    assert_eq!(err.suggestions(), ["--no-cache"]);
+}
+
+#[test]
+fn option_arity_exactly() {
+    let parser = || ArgParser::new()
+        .flag("flag f")
+        .option("point p", "")
+        .arity("point", Arity::Exactly(2));
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--point", "1", "-2", "-p", "3", "4", "foo"]).is_ok());
+    assert_eq!(p.occurrences("point"), vec![vec!["1", "-2"], vec!["3", "4"]]);
+    assert_eq!(p.values("point"), vec!["1", "-2", "3", "4"]);
+    assert_eq!(p.count("point"), 2);
+    assert_eq!(p.args, vec!["foo"]);
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--point=1", "2"]).is_ok());
+    assert_eq!(p.occurrences("p"), vec![vec!["1", "2"]]);
+    let err = parser().parse_vec(vec!["--point", "1"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MissingValue);
+    assert_eq!(err.message(), "missing value for --point (expected 2 values, found 1)");
+    let err = parser().parse_vec(vec!["-fp", "1"]).unwrap_err();
+    assert_eq!(err.message(), "missing value for 'p' in -fp (expected 2 values, found 1)");
+}
+
+#[test]
+fn option_arity_greedy() {
+    let parser = || ArgParser::new()
+        .flag("flag f")
+        .option("files", "")
+        .arity("files", Arity::Variadic)
+        .option("pair", "")
+        .arity("pair", Arity::AtLeast(2));
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--files", "a", "b", "-f", "--files", "--pair", "x", "-y", "z", "--", "w"]).is_ok());
+    assert_eq!(p.occurrences("files"), vec![vec!["a", "b"], vec![]]);
+    assert_eq!(p.occurrences("pair"), vec![vec!["x", "-y", "z"]]);
+    assert!(p.found("flag"));
+    assert_eq!(p.args, vec!["w"]);
+    let err = parser().parse_vec(vec!["--pair", "x"]).unwrap_err();
+    assert_eq!(err.message(), "missing value for --pair (expected at least 2 values, found 1)");
+}
+
+#[test]
+fn option_arity_helptext() {
+    let parser = ArgParser::new()
+        .name("app")
+        .generate_helptext(true)
+        .option("point", "")
+        .arity("point", Arity::Exactly(2))
+        .option("files", "")
+        .arity("files", Arity::Variadic)
+        .option("pair", "")
+        .arity("pair", Arity::AtLeast(2));
+    let helptext = parser.render_helptext();
+    assert!(helptext.contains("--point <arg> <arg>"));
+    assert!(helptext.contains("--files [<arg>...]"));
+    assert!(helptext.contains("--pair <arg> <arg>..."));
+}
+
+#[test]
+fn option_delimiter() {
+    let parser = || ArgParser::new()
+        .option("tags t", "")
+        .delimiter("tags", ',')
+        .option("point", "")
+        .arity("point", Arity::Exactly(2))
+        .delimiter("point", ':');
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--tags", "a,b,c", "-t", "d"]).is_ok());
+    assert_eq!(p.values("tags"), vec!["a", "b", "c", "d"]);
+    assert_eq!(p.occurrences("tags"), vec![vec!["a", "b", "c"], vec!["d"]]);
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--tags=a\\,b,c\\\\,,d\\e"]).is_ok());
+    assert_eq!(p.values("tags"), vec!["a,b", "c\\", "", "d\\e"]);
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--point", "1:2", "3"]).is_ok());
+    assert_eq!(p.values("point"), vec!["1", "2", "3"]);
+}
+
+#[test]
+fn option_delimiter_fallbacks() {
+    let path = write_temp_file("delimiter.conf", "tags = 'c,d'\n");
+    let mut parser = ArgParser::new()
+        .option("opt", "")
+        .delimiter("opt", ',')
+        .env("opt", "TEST_LIST")
+        .option("tags", "")
+        .delimiter("tags", ',')
+        .env_source(|var| match var {
+            "TEST_LIST" => Some("a,b".to_string()),
+            _ => None,
+        })
+        .config_file(&path);
+    assert!(parser.parse_vec(vec![]).is_ok());
+    assert_eq!(parser.values("opt"), vec!["a", "b"]);
+    assert_eq!(parser.values("tags"), vec!["c", "d"]);
+}
+
+#[test]
+fn global_options() {
+    let parser = || ArgParser::new()
+        .flag("verbose v")
+        .global("verbose")
+        .option("color", "auto")
+        .global("color")
+        .option("local", "")
+        .command("remote", ArgParser::new()
+            .command("add", ArgParser::new().flag("force f")));
+    let mut p = parser();
+    p.parse_vec(vec!["-v", "remote", "add", "--color", "never", "-fv"]).unwrap();
+    assert_eq!(p.count("verbose"), 2);
+    assert_eq!(p.value("color"), "never");
+    let remote = p.cmd_parser.unwrap();
+    assert_eq!(remote.count("verbose"), 2);
+    let add = remote.cmd_parser.unwrap();
+    assert_eq!(add.count("verbose"), 2);
+    assert_eq!(add.value("color"), "never");
+    assert!(add.found("force"));
+    let err = parser().parse_vec(vec!["remote", "--local", "x"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidName);
+}
+
+#[test]
+fn global_options_shadowed() {
+    let mut parser = ArgParser::new()
+        .option("output o", "")
+        .global("output")
+        .command("build", ArgParser::new().flag("o"));
+    assert!(parser.parse_vec(vec!["build", "-o"]).is_ok());
+    assert!(!parser.found("output"));
+    assert!(parser.cmd_parser.unwrap().found("o"));
+}
+
+#[test]
+fn global_options_config() {
+    let path = write_temp_file("global.conf", "color = always\n[build]\njobs = 4\n");
+    let mut parser = ArgParser::new()
+        .option("color", "auto")
+        .global("color")
+        .option("jobs", "1")
+        .config_file(&path)
+        .command("build", ArgParser::new().option("jobs", "1"));
+    assert!(parser.parse_vec(vec!["build"]).is_ok());
+    assert_eq!(parser.value("color"), "always");
+    assert_eq!(parser.value("jobs"), "1");
+    let build = parser.cmd_parser.unwrap();
+    assert_eq!(build.value("color"), "always");
+    assert_eq!(build.value("jobs"), "4");
+}
+
+#[test]
+fn command_after_flags() {
+    let mut parser = ArgParser::new()
+        .flag("verbose v")
+        .command("build", ArgParser::new());
+    assert!(parser.parse_vec(vec!["-v", "build"]).is_ok());
+    assert_eq!(parser.cmd_name, Some("build".to_string()));
+    let mut parser = ArgParser::new()
+        .command("build", ArgParser::new());
+    assert!(parser.parse_vec(vec!["x", "build"]).is_ok());
+    assert_eq!(parser.args, vec!["x", "build"]);
+}
+
+#[test]
+fn command_path_and_lookup() {
+    let parser = || ArgParser::new()
+        .command("remote r", ArgParser::new()
+            .command("add", ArgParser::new().option("name", ""))
+            .command("remove rm", ArgParser::new()));
+    let p = parser();
+    assert!(p.command_path().is_empty());
+    assert!(std::ptr::eq(p.leaf(), &p));
+    assert!(p.find_command(&["r", "rm"]).is_some());
+    assert!(p.find_command(&["remote", "list"]).is_none());
+    assert!(std::ptr::eq(p.find_command(&[]).unwrap(), &p));
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["r", "add", "--name", "origin"]).is_ok());
+    assert_eq!(p.command_path(), vec!["remote", "add"]);
+    assert_eq!(p.leaf().value("name"), "origin");
+    assert_eq!(p.find_command(&["remote", "add"]).unwrap().value("name"), "origin");
+    assert!(p.find_command(&["remote", "rm"]).is_none());
+}
+
+#[test]
+fn abbreviations() {
+    let parser = || ArgParser::new()
+        .allow_abbreviations(true)
+        .flag("verbose v")
+        .negatable("verbose")
+        .flag("verb")
+        .option("output out o", "")
+        .version("1.0")
+        .exit_on_help(false)
+        .command("status", ArgParser::new().flag("short"))
+        .command("start", ArgParser::new());
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--verb", "--verbo", "--outp=a", "--ou", "b", "--no-v", "stat", "--sh"]).is_ok());
+    assert_eq!(p.count("verb"), 1);
+    assert_eq!(p.count("verbose"), 1);
+    assert!(p.negated("verbose"));
+    assert_eq!(p.values("output"), vec!["a", "b"]);
+    assert_eq!(p.cmd_name, Some("status".to_string()));
+    assert!(p.cmd_parser.unwrap().found("short"));
+    assert_eq!(parser().parse_vec(vec!["--vers"]).unwrap_err().kind(), ErrorKind::VersionRequested);
+
+    let err = parser().parse_vec(vec!["--ve"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidName);
+    assert_eq!(err.message(), "--ve is ambiguous (did you mean --verb, --verbose or --version?)");
+    assert_eq!(err.suggestions(), ["--verb", "--verbose", "--version"]);
+    let err = parser().parse_vec(vec!["st"]).unwrap_err();
+    assert_eq!(err.suggestions(), ["start", "status"]);
+    assert_eq!(err.token(), Some("st"));
+
+    let mut p = ArgParser::new().flag("verbose");
+    assert_eq!(p.parse_vec(vec!["--verb"]).unwrap_err().kind(), ErrorKind::InvalidName);
+}
+
+#[test]
+fn command_handler_after_validation() {
+    let called = std::rc::Rc::new(std::cell::Cell::new(false));
+    let flag = called.clone();
+    let mut parser = ArgParser::new()
+        .option("token", "")
+        .required("token")
+        .command("rm", ArgParser::new().callback(move |_, _| flag.set(true)));
+    let err = parser.parse_vec(vec!["rm"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MissingRequired);
+    assert!(!called.get());
+    let mut parser = ArgParser::new()
+        .option("token", "")
+        .required("token")
+        .command("rm", ArgParser::new().callback({
+            let called = called.clone();
+            move |_, _| called.set(true)
+        }));
+    assert!(parser.parse_vec(vec!["--token", "x", "rm"]).is_ok());
+    assert!(called.get());
+}
+
+#[test]
+fn command_arg_limits() {
+    let mut parser = ArgParser::new()
+        .min_args(1)
+        .max_args(1)
+        .command("b", ArgParser::new());
+    assert!(parser.parse_vec(vec!["b", "x", "y"]).is_ok());
+    assert_eq!(parser.cmd_parser.unwrap().args, vec!["x", "y"]);
+    let mut parser = ArgParser::new().min_args(1).command("b", ArgParser::new());
+    assert_eq!(parser.parse_vec(vec![]).unwrap_err().kind(), ErrorKind::TooFewArgs);
+}
+
+#[test]
+fn constraint_aliases() {
+    let mut parser = ArgParser::new()
+        .option("output o", "")
+        .flag("quiet q")
+        .conflicts(&["output", "o", "quiet"]);
+    assert!(parser.parse_vec(vec!["-o", "x"]).is_ok());
+    let mut parser = ArgParser::new()
+        .option("output o", "")
+        .flag("quiet q")
+        .conflicts(&["output", "o", "q"]);
+    let err = parser.parse_vec(vec!["-o", "x", "-q"]).unwrap_err();
+    assert_eq!(err.message(), "--output and --quiet cannot be used together");
+}
+
+#[test]
+fn constraint_command_line_overrides_fallbacks() {
+    let parser = || ArgParser::new()
+        .flag("json")
+        .env("json", "TEST_FLAG")
+        .flag("csv")
+        .conflicts(&["json", "csv"])
+        .env_source(test_env);
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["--csv"]).is_ok());
+    assert!(!p.found("json"));
+    let mut p = parser();
+    assert!(p.parse_vec(vec![]).is_ok());
+    assert!(p.found("json"));
+    assert_eq!(parser().parse_vec(vec!["--json", "--csv"]).unwrap_err().kind(), ErrorKind::Conflict);
+}
+
+#[test]
+fn constraint_checked_before_handlers() {
+    let called = std::rc::Rc::new(std::cell::Cell::new(false));
+    let flag = called.clone();
+    let mut parser = ArgParser::new()
+        .flag("a")
+        .flag("b")
+        .conflicts(&["a", "b"])
+        .command("rm", ArgParser::new().callback(move |_, _| flag.set(true)));
+    assert_eq!(parser.parse_vec(vec!["-a", "-b", "rm"]).unwrap_err().kind(), ErrorKind::Conflict);
+    assert!(!called.get());
+}
+
+#[test]
+fn global_options_completion_and_manpages() {
+    let parser = ArgParser::new()
+        .name("app")
+        .flag("verbose")
+        .global("verbose")
+        .option("color", "")
+        .global("color")
+        .command("build", ArgParser::new()
+            .flag("release")
+            .option("color", "")
+            .command("docs", ArgParser::new()));
+    let script = parser.completion_script(Shell::Bash);
+    assert!(script.contains("app_build) words=\"--release --verbose --color docs\" ;;"));
+    assert!(script.contains("app_build_docs) words=\"--verbose\" ;;"));
+    let pages = parser.manpages();
+    assert_eq!(pages.len(), 3);
+    assert!(pages.iter().all(|page| page.content.contains("\\fB\\-\\-verbose\\fR")));
+    assert_eq!(pages[1].content.matches("\\fB\\-\\-color\\fR").count(), 1);
+}
+
+#[test]
+fn global_options_fallbacks() {
+    let parser = || ArgParser::new()
+        .flag("json")
+        .env("json", "TEST_FLAG")
+        .global("json")
+        .flag("csv")
+        .global("csv")
+        .conflicts(&["json", "csv"])
+        .option("token", "")
+        .env("token", "TEST_OPT")
+        .required("token")
+        .global("token")
+        .env_source(test_env)
+        .command("build", ArgParser::new());
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["build", "--csv"]).is_ok());
+    assert!(!p.found("json"));
+    assert_eq!(p.value("token"), "env");
+    let build = p.cmd_parser.unwrap();
+    assert!(!build.found("json"));
+    assert!(build.found("csv"));
+    assert_eq!(build.value("token"), "env");
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["build"]).is_ok());
+    assert!(p.cmd_parser.unwrap().found("json"));
+}
+
+#[test]
+fn abbreviations_hidden_commands() {
+    let parser = || ArgParser::new()
+        .allow_abbreviations(true)
+        .enable_completion_command(true)
+        .exit_on_help(false)
+        .command("commit", ArgParser::new())
+        .command("completions", ArgParser::new());
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["comm"]).is_ok());
+    assert_eq!(p.cmd_name, Some("commit".to_string()));
+    let err = parser().parse_vec(vec!["co"]).unwrap_err();
+    assert_eq!(err.suggestions(), ["commit", "completions"]);
+    assert_eq!(parser().parse_vec(vec!["c", "bash"]).unwrap_err().kind(), ErrorKind::InvalidName);
+    let err = parser().parse_vec(vec!["completion", "bash"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::CompletionRequested);
+}
+
+#[test]
+fn empty_names_accepted() {
+    let mut parser = ArgParser::new()
+        .option("", "default")
+        .flag("")
+        .flag("json")
+        .flag("csv")
+        .conflicts(&["json", "csv"]);
+    assert!(parser.parse_vec(vec!["--json", "arg"]).is_ok());
+    assert_eq!(parser.args, vec!["arg"]);
+}
+
+#[test]
+fn config_command_files() {
+    let file = write_temp_file("command.conf", "[build]\nout = fromfile\njobs = 2\n");
+    let option = write_temp_file("command-option.conf", "[build]\nout = fromoption\n");
+    let parser = || ArgParser::new()
+        .command("build", ArgParser::new()
+            .option("out", "def")
+            .option("jobs", "1")
+            .option("config", "")
+            .config_option("config")
+            .config_file(&file));
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["build"]).is_ok());
+    assert_eq!(p.cmd_parser.unwrap().value("out"), "fromfile");
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["build", "--config", option.to_str().unwrap()]).is_ok());
+    let build = p.cmd_parser.unwrap();
+    assert_eq!(build.value("out"), "fromoption");
+    assert_eq!(build.value("jobs"), "2");
+    let err = parser().parse_vec(vec!["build", "--config", "/nonexistent/arguably.conf"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidConfig);
+    assert_eq!(err.command_path(), ["build"]);
+}
+
+#[test]
+fn config_option_env() {
+    let path = write_temp_file("env-option.conf", "out = fromfile\n");
+    let var = path.to_str().unwrap().to_string();
+    let mut parser = ArgParser::new()
+        .option("out", "def")
+        .option("config", "")
+        .env("config", "APP_CONFIG")
+        .config_option("config")
+        .env_source(move |name| if name == "APP_CONFIG" { Some(var.clone()) } else { None });
+    assert!(parser.parse_vec(vec![]).is_ok());
+    assert_eq!(parser.value("out"), "fromfile");
+}
+
+#[test]
+fn config_option_global() {
+    let path = write_temp_file("global-option.conf", "out = root\n[build]\nout = fromfile\n");
+    let parser = || ArgParser::new()
+        .option("out", "def")
+        .option("config", "")
+        .global("config")
+        .config_option("config")
+        .command("build", ArgParser::new()
+            .option("out", "def")
+            .command("docs", ArgParser::new().option("out", "def")));
+    for args in [["--config", path.to_str().unwrap(), "build"], ["build", "--config", path.to_str().unwrap()]] {
+        let mut p = parser();
+        assert!(p.parse_vec(args.to_vec()).is_ok());
+        assert_eq!(p.value("out"), "root");
+        assert_eq!(p.cmd_parser.unwrap().value("out"), "fromfile");
+    }
+    let mut p = parser();
+    assert!(p.parse_vec(vec!["build", "docs", "--config", path.to_str().unwrap()]).is_ok());
+    assert_eq!(p.value("out"), "root");
+    assert_eq!(p.cmd_parser.unwrap().value("out"), "fromfile");
+}